@@ -0,0 +1,141 @@
+//! Coordinate transforms of whole chunks of points at once.
+
+use std::iter::FromIterator;
+
+/// Points in structure-of-arrays layout, so transforms over them are vectorized by the compiler.
+#[derive(Clone, Debug, Default)]
+pub struct Batch {
+    pub x: Vec<f64>,
+    pub y: Vec<f64>,
+    pub z: Vec<f64>,
+}
+
+/// An affine transform between two coordinate systems, as the rows of a 3x4 matrix.
+#[derive(Clone, Copy, Debug)]
+pub struct Affine {
+    rows: [[f64; 4]; 3],
+}
+
+impl Batch {
+    /// Returns the number of points.
+    pub fn len(&self) -> usize {
+        self.x.len()
+    }
+
+    /// Returns true if there are no points.
+    pub fn is_empty(&self) -> bool {
+        self.x.is_empty()
+    }
+
+    /// Returns the coordinates of the i-th point.
+    pub fn get(&self, i: usize) -> (f64, f64, f64) {
+        (self.x[i], self.y[i], self.z[i])
+    }
+
+    /// Removes every point, keeping the allocations for the next chunk.
+    pub fn clear(&mut self) {
+        self.x.clear();
+        self.y.clear();
+        self.z.clear();
+    }
+}
+
+impl Extend<(f64, f64, f64)> for Batch {
+    fn extend<I: IntoIterator<Item = (f64, f64, f64)>>(&mut self, iter: I) {
+        for (x, y, z) in iter {
+            self.x.push(x);
+            self.y.push(y);
+            self.z.push(z);
+        }
+    }
+}
+
+impl FromIterator<(f64, f64, f64)> for Batch {
+    fn from_iter<I: IntoIterator<Item = (f64, f64, f64)>>(iter: I) -> Batch {
+        let mut batch = Batch::default();
+        batch.extend(iter);
+        batch
+    }
+}
+
+impl Affine {
+    /// Captures a transform from where it takes the origin and the unit vectors.
+    ///
+    /// This is exact for affine transforms, e.g. the chain of rigid-body transforms from a
+    /// scanner's own coordinate system to a camera's.
+    pub fn from_fn<F>(transform: F) -> Affine
+    where
+        F: Fn(f64, f64, f64) -> (f64, f64, f64),
+    {
+        let origin = transform(0., 0., 0.);
+        let x = transform(1., 0., 0.);
+        let y = transform(0., 1., 0.);
+        let z = transform(0., 0., 1.);
+        Affine {
+            rows: [
+                [x.0 - origin.0, y.0 - origin.0, z.0 - origin.0, origin.0],
+                [x.1 - origin.1, y.1 - origin.1, z.1 - origin.1, origin.1],
+                [x.2 - origin.2, y.2 - origin.2, z.2 - origin.2, origin.2],
+            ],
+        }
+    }
+
+    /// Transforms every point of a batch into `transformed`, replacing its points but reusing its
+    /// allocations.
+    pub fn apply(&self, batch: &Batch, transformed: &mut Batch) {
+        self.apply_row(0, batch, &mut transformed.x);
+        self.apply_row(1, batch, &mut transformed.y);
+        self.apply_row(2, batch, &mut transformed.z);
+    }
+
+    /// Computes one output coordinate of every point, in a loop simple enough to vectorize.
+    fn apply_row(&self, row: usize, batch: &Batch, coordinates: &mut Vec<f64>) {
+        let (a, b, c, d) = (
+            self.rows[row][0],
+            self.rows[row][1],
+            self.rows[row][2],
+            self.rows[row][3],
+        );
+        coordinates.clear();
+        coordinates.extend(
+            batch
+                .x
+                .iter()
+                .zip(batch.y.iter())
+                .zip(batch.z.iter())
+                .map(|((&x, &y), &z)| a * x + b * y + c * z + d),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use matrix;
+    use riscan_pro::Point;
+
+    #[test]
+    fn matches_per_point_transform() {
+        let sop = matrix::parse("0.8 -0.6 0 100  0.6 0.8 0 200  0 0 1 10  0 0 0 1").unwrap();
+        let pop = matrix::parse("0 -1 0 500000  1 0 0 7000000  0 0 1 -5  0 0 0 1").unwrap();
+        let to_glcs = |x, y, z| *Point::socs(x, y, z).to_prcs(sop).to_glcs(pop);
+        let affine = Affine::from_fn(|x, y, z| {
+            let glcs = to_glcs(x, y, z);
+            (glcs.x, glcs.y, glcs.z)
+        });
+        let batch = vec![(0., 0., 0.), (1.5, -2.25, 3.), (-120.5, 40.25, -7.75)]
+            .into_iter()
+            .collect::<Batch>();
+        let mut transformed = Batch::default();
+        affine.apply(&batch, &mut transformed);
+        assert_eq!(batch.len(), transformed.len());
+        for i in 0..batch.len() {
+            let (x, y, z) = batch.get(i);
+            let expected = to_glcs(x, y, z);
+            let actual = transformed.get(i);
+            assert!((actual.0 - expected.x).abs() < 1e-6, "{:?} {:?}", actual, expected);
+            assert!((actual.1 - expected.y).abs() < 1e-6, "{:?} {:?}", actual, expected);
+            assert!((actual.2 - expected.z).abs() < 1e-6, "{:?} {:?}", actual, expected);
+        }
+    }
+}
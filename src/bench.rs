@@ -0,0 +1,130 @@
+use std::time::{Duration, Instant};
+
+/// A stage of colorizing a point.
+#[derive(Clone, Copy, Debug)]
+pub enum Stage {
+    /// Reading the point from the input file.
+    Reading,
+    /// Transforming chunks of points from SOCS into the project's global coordinate system.
+    Coordinates,
+    /// Projecting the point from SOCS into each image's CMCS and pixel coordinates.
+    Projection,
+    /// Looking up the temperatures (and baselines) of the point's pixels.
+    Lookup,
+    /// Transforming the point into the output coordinate system and mapping it to colors.
+    Transform,
+    /// Writing the point to the output file(s).
+    Writing,
+}
+
+const STAGES: [(Stage, &'static str); 6] = [
+    (Stage::Reading, "read"),
+    (Stage::Coordinates, "SOCS to GLCS"),
+    (Stage::Projection, "SOCS to CMCS"),
+    (Stage::Lookup, "pixel lookup"),
+    (Stage::Transform, "transform"),
+    (Stage::Writing, "write"),
+];
+
+/// Accumulates the time spent in each stage, for `--bench` and `--timings`.
+///
+/// Does nothing if it isn't enabled, so it can stay in the colorization loop for free.
+#[derive(Debug)]
+pub struct Bench {
+    enabled: bool,
+    last: Instant,
+    durations: [Duration; 6],
+}
+
+impl Bench {
+    /// Creates a new bench.
+    pub fn new(enabled: bool) -> Bench {
+        Bench {
+            enabled: enabled,
+            last: Instant::now(),
+            durations: [Duration::default(); 6],
+        }
+    }
+
+    /// Starts timing a point.
+    pub fn start(&mut self) {
+        if self.enabled {
+            self.last = Instant::now();
+        }
+    }
+
+    /// Adds the time since the start, or the last lap, to a stage.
+    pub fn lap(&mut self, stage: Stage) {
+        if !self.enabled {
+            return;
+        }
+        let now = Instant::now();
+        self.durations[stage as usize] += now - self.last;
+        self.last = now;
+    }
+
+    /// Adds the time spent in another bench, e.g. from another stage's thread, to this one.
+    pub fn merge(&mut self, other: &Bench) {
+        for (duration, other) in self.durations.iter_mut().zip(other.durations.iter()) {
+            *duration += *other;
+        }
+    }
+
+    /// Returns a description of the throughput of reading, coordinate transforms, projection
+    /// (including lookup), and color mapping, given the number of points that went through each.
+    pub fn report(&self, read: u64, projected: u64, colored: u64) -> String {
+        format!(
+            "reading {}, coordinate transforms {}, projection {}, color mapping {}",
+            throughput(read, self.duration(Stage::Reading)),
+            throughput(read, self.duration(Stage::Coordinates)),
+            throughput(
+                projected,
+                self.duration(Stage::Projection) + self.duration(Stage::Lookup)
+            ),
+            throughput(colored, self.duration(Stage::Transform))
+        )
+    }
+
+    /// Returns a description of the time spent in each stage, and its share of the total.
+    pub fn timings(&self) -> String {
+        let total = seconds(self.durations.iter().fold(Duration::default(), |a, &b| a + b));
+        STAGES
+            .iter()
+            .map(|&(stage, name)| {
+                let seconds = seconds(self.duration(stage));
+                let percent = if total > 0. {
+                    100. * seconds / total
+                } else {
+                    0.
+                };
+                format!("{} {:.2}s ({:.0}%)", name, seconds, percent)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Returns the seconds spent in each stage, keyed by stage name.
+    pub fn seconds(&self) -> Vec<(&'static str, f64)> {
+        STAGES
+            .iter()
+            .map(|&(stage, name)| (name, seconds(self.duration(stage))))
+            .collect()
+    }
+
+    fn duration(&self, stage: Stage) -> Duration {
+        self.durations[stage as usize]
+    }
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
+}
+
+fn throughput(count: u64, duration: Duration) -> String {
+    let seconds = seconds(duration);
+    if seconds > 0. {
+        format!("{:.0} points/s", count as f64 / seconds)
+    } else {
+        "n/a".to_string()
+    }
+}
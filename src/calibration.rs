@@ -0,0 +1,168 @@
+//! Checks a project's camera calibrations against targets with known positions.
+
+use clap::ArgMatches;
+use distortion::Distortion;
+use project;
+use riscan_pro::Point;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Error, ErrorKind};
+use std::path::Path;
+
+/// A target, e.g. a reflector, seen in an image.
+struct Target {
+    scan_position: String,
+    image: String,
+    name: String,
+    /// The target's position in the scanner's own coordinate system.
+    socs: (f64, f64, f64),
+    /// Where the target was picked in the image file, in pixels.
+    pixel: (f64, f64),
+}
+
+/// Projects each target through its image's calibration chain (SOCS to CMCS to ICS to the image
+/// file) and reports the distance to where it was picked, per target and per camera and mount
+/// calibration.
+///
+/// The projection is the one used to colorize, with the same `--camera-calibration` overrides,
+/// `--distortion-dir`, `--tilt-images`, and image file orientation.
+///
+/// Calibrations whose root mean square residual is above `--max-residual` are flagged, as are
+/// targets that don't project into their image at all.
+pub fn check(matches: &ArgMatches) {
+    let mut project = project::open(matches.value_of("PROJECT").unwrap());
+    ::override_camera_calibrations(matches, &mut project);
+    let distortions = matches
+        .value_of("distortion-dir")
+        .map(|dir| ::per_camera(dir, |path| Distortion::from_path(path)))
+        .unwrap_or_default();
+    let tilt_images = ::patterns(matches, "tilt-images");
+    let tilt_mount = ::tilt_mount(matches, &project);
+    let position_rotations = ::position_rotations(matches, &project);
+    let file_orientation = ::orientation(matches);
+    let max_residual = value_t!(matches, "max-residual", f64).unwrap();
+    let path = matches.value_of("TARGETS").unwrap();
+    let targets = read_targets(path).expect(&format!("Could not read targets {}", path));
+    let mut residuals: BTreeMap<(String, String), Vec<f64>> = BTreeMap::new();
+    info!("Targets:");
+    for target in &targets {
+        let scan_position = project
+            .scan_positions
+            .get(&target.scan_position)
+            .expect(&format!("No scan position named {}", target.scan_position));
+        let image = scan_position.images.get(&target.image).expect(&format!(
+            "Could not find image {} in scan position {}",
+            target.image,
+            target.scan_position
+        ));
+        let camera_calibration = image.camera_calibration(&project).unwrap();
+        // Targets don't have image files, so --tilt-images is matched against the image name.
+        let mount_calibration = match tilt_mount.as_ref() {
+            Some(tilt_mount) if ::image_matches(&tilt_images, Path::new(&image.name), image) => {
+                &project.mount_calibrations[tilt_mount]
+            }
+            _ => image.mount_calibration(&project).unwrap(),
+        };
+        let mut orientation = file_orientation;
+        if let Some(&rotation) = position_rotations.get(&scan_position.name) {
+            orientation.rotation = rotation;
+        }
+        let (x, y, z) = target.socs;
+        let cmcs = Point::socs(x, y, z).to_cmcs(image.cop, mount_calibration);
+        let description = format!("{} in {}/{}", target.name, target.scan_position, target.image);
+        let pixel = ::file_pixel(
+            &cmcs,
+            camera_calibration,
+            distortions.get(&camera_calibration.name),
+            &orientation,
+        );
+        match pixel {
+            Some((x, y)) => {
+                // Measured from the center of the pixel that the target is colorized from.
+                let (u, v) = (f64::from(x) + 0.5, f64::from(y) + 0.5);
+                let residual = ((u - target.pixel.0).powi(2) + (v - target.pixel.1).powi(2)).sqrt();
+                info!(
+                    "  - {}: projected to pixel ({}, {}), picked at ({:.1}, {:.1}), {:.2} px",
+                    description,
+                    x,
+                    y,
+                    target.pixel.0,
+                    target.pixel.1,
+                    residual
+                );
+                residuals
+                    .entry((
+                        camera_calibration.name.clone(),
+                        mount_calibration.name.clone(),
+                    ))
+                    .or_insert_with(Vec::new)
+                    .push(residual);
+            }
+            None => warn!("{} doesn't project into the image", description),
+        }
+    }
+    info!("Calibrations:");
+    for (&(ref camera, ref mount), residuals) in &residuals {
+        let rms =
+            (residuals.iter().map(|r| r * r).sum::<f64>() / residuals.len() as f64).sqrt();
+        let max = residuals.iter().cloned().fold(0., f64::max);
+        info!(
+            "  - camera {}, mount {}: {} targets, rms {:.2} px, max {:.2} px",
+            camera,
+            mount,
+            residuals.len(),
+            rms,
+            max
+        );
+        if rms > max_residual {
+            warn!(
+                "camera {} with mount {} has an rms residual of {:.2} px, above {} px",
+                camera,
+                mount,
+                rms,
+                max_residual
+            );
+        }
+    }
+}
+
+/// Reads a csv file of `scan position, image name, target name, x, y, z, u, v` rows, where x, y,
+/// and z are in SOCS and u and v are pixels of the image file.
+///
+/// Blank lines and lines starting with `#` are ignored.
+fn read_targets(path: &str) -> io::Result<Vec<Target>> {
+    let mut targets = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line.split(',').map(|field| field.trim()).collect::<Vec<_>>();
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "expected scan position, image name, target name, x, y, z, u, and v, got: {}",
+                    line
+                ),
+            )
+        };
+        if fields.len() != 8 {
+            return Err(invalid());
+        }
+        let values = fields[3..]
+            .iter()
+            .map(|field| field.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|_| invalid())?;
+        targets.push(Target {
+            scan_position: fields[0].to_string(),
+            image: fields[1].to_string(),
+            name: fields[2].to_string(),
+            socs: (values[0], values[1], values[2]),
+            pixel: (values[3], values[4]),
+        });
+    }
+    Ok(targets)
+}
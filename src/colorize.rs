@@ -0,0 +1,683 @@
+//! The colorization pipeline, which reads an input file's points, projects them into the thermal
+//! images, and writes them with their temperatures.
+
+use batch::{Affine, Batch};
+use bench::{Bench, Stage};
+use crossbeam;
+use footprint::Footprint;
+use las::{self, Color};
+use las::raw::point::Waveform;
+use logger;
+use lut::Lookup;
+use nalgebra::Point3;
+use ortho::Ortho;
+use preview::Preview;
+use quantile::Quantile;
+use rejections::Rejections;
+use riscan_pro::{Cmcs, Point, ScanPosition};
+use serde_json;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::panic;
+use std::sync::mpsc::sync_channel;
+use summary::{Counts, NoThermal};
+use {Colorization, Config, ImageGroup, Offset, OutputPoint, PIPELINE_DEPTH, PROGRESS_INTERVAL,
+     RANGE_QUANTILES, ScanPoint, Translation};
+
+impl Config {
+    /// Colorizes one input file.
+    ///
+    /// Points are read on one thread, projected into the images on this one, and written on
+    /// another, with bounded channels of point chunks between them so reading and writing overlap
+    /// with projection without buffering the whole file.
+    ///
+    /// With `keep_going`, reading stops at the first point that can't be read, and the points
+    /// before it are written as usual.
+    pub fn colorize(
+        &self,
+        scan_position: &ScanPosition,
+        translation: &Translation,
+        keep_going: bool,
+    ) -> Colorization {
+        use std::f64;
+
+        let mut image_groups = self.image_groups(scan_position, &translation.image_dir)
+            .into_iter()
+            .filter(|image_group| {
+                self.is_within_time_offset(&image_group.irb_path, &translation.infile)
+            })
+            .collect::<Vec<_>>();
+        let mut baseline_image_groups = self.delta_dir.as_ref().map(|delta_dir| {
+            self.image_groups(scan_position, delta_dir)
+        });
+        {
+            let all_image_groups = image_groups.iter_mut().chain(
+                baseline_image_groups.iter_mut().flat_map(|image_groups| image_groups.iter_mut()),
+            );
+            self.preload(all_image_groups);
+        }
+        let rgb_image_groups = self.rgb_image_groups(scan_position);
+        debug!(
+            "    - {} thermal images, {} rgb images",
+            image_groups.len(),
+            rgb_image_groups.len()
+        );
+        let mut calibrations = BTreeMap::new();
+        for image_group in &image_groups {
+            *calibrations
+                .entry((
+                    &image_group.camera_calibration.name,
+                    &image_group.mount_calibration.name,
+                ))
+                .or_insert(0) += 1;
+        }
+        for (&(camera, mount), count) in &calibrations {
+            debug!("      - {} images from camera {} on mount {}", count, camera, mount);
+        }
+        for image_group in &image_groups {
+            trace!("      - {}", image_group.irb_path.display());
+        }
+        if self.image_stats {
+            for image_group in &image_groups {
+                image_group.log_stats();
+            }
+        }
+        let mut ortho = self.ortho_resolution.map(Ortho::new);
+        let mut footprint = Footprint::default();
+        let mut preview = self.preview_width.map(Preview::new);
+        let mut coverages = if self.coverage {
+            image_groups
+                .iter()
+                .map(|image_group| {
+                    let (width, height) = image_group.file_dimensions();
+                    vec![0u32; width * height]
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let mut rejections = if self.rejections {
+            image_groups
+                .iter()
+                .map(|image_group| Rejections::new(image_group.camera_calibration))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let chunk_len = self.chunk_len();
+        let extra_attributes = self.extra_attributes();
+        let timed = self.bench || self.timings;
+        let (read_sender, read_receiver) = sync_channel::<Vec<ScanPoint>>(PIPELINE_DEPTH);
+        let (write_sender, write_receiver) = sync_channel::<Vec<OutputPoint>>(PIPELINE_DEPTH);
+        let progress = self.progress_bar(&translation.infile);
+        let mut counts = Counts::default();
+        let mut histogram = self.histogram();
+        let mut range = (Quantile::new(RANGE_QUANTILES.0), Quantile::new(RANGE_QUANTILES.1));
+        let mut bench = Bench::new(timed);
+        let read_error = crossbeam::scope(|scope| {
+            // Reads the input file, in chunks.
+            let reading = scope.spawn(move || {
+                let mut bench = Bench::new(timed);
+                let mut read_error = None;
+                let filtered = Cell::new(0);
+                let mut points =
+                    self.selected_points(scan_position, &translation.infile, &filtered);
+                loop {
+                    bench.start();
+                    let mut chunk = Vec::with_capacity(chunk_len);
+                    for point in points.by_ref().take(chunk_len) {
+                        match point {
+                            Ok(point) => chunk.push(point),
+                            Err(message) => {
+                                if !keep_going {
+                                    panic!("{}", message);
+                                }
+                                read_error = Some(message);
+                                break;
+                            }
+                        }
+                    }
+                    bench.lap(Stage::Reading);
+                    if chunk.is_empty() || read_sender.send(chunk).is_err() ||
+                        read_error.is_some()
+                    {
+                        break;
+                    }
+                }
+                (bench, read_error, filtered.get())
+            });
+            let first_chunk = read_receiver.recv().unwrap_or_default();
+            let offset = match self.offset {
+                Offset::Pop => {
+                    self.to_output(&Point::prcs(0., 0., 0.).to_glcs(self.project.pop))
+                }
+                Offset::Values(x, y, z) => Point3::new(x, y, z),
+                Offset::Auto => self.auto_offset(scan_position, &first_chunk),
+            };
+            if let Some(parent) = translation.outfile.parent() {
+                fs::create_dir_all(parent)
+                    .expect(&format!("Could not create {}", parent.display()));
+            }
+            let header = self.las_header(offset, scan_position, translation, &image_groups);
+            let format = header.point_format;
+            let mut writer = if self.bench {
+                None
+            } else {
+                Some(self.las_writer(&translation.outfile, &header))
+            };
+            let mut rgb_writer = translation
+                .rgb_outfile
+                .as_ref()
+                .filter(|_| !self.bench)
+                .map(|rgb_outfile| self.las_writer(rgb_outfile, &header));
+            let mut split_writers = translation
+                .split_outfiles
+                .iter()
+                .filter(|_| !self.bench)
+                .map(|split_outfile| self.las_writer(split_outfile, &header))
+                .collect::<Vec<_>>();
+
+            // Writes the colorized points to the output files.
+            let writing = scope.spawn(move || {
+                let mut bench = Bench::new(timed);
+                for chunk in write_receiver {
+                    bench.start();
+                    for output_point in chunk {
+                        if let (Some(rgb_writer), Some(rgb_point)) =
+                            (rgb_writer.as_mut(), output_point.rgb_point)
+                        {
+                            rgb_writer.write(rgb_point).expect("could not write las point");
+                        }
+                        if let Some(split_writer) = output_point
+                            .split_index
+                            .and_then(|index| split_writers.get_mut(index))
+                        {
+                            split_writer
+                                .write(output_point.point.clone())
+                                .expect("could not write las point");
+                        }
+                        if let Some(writer) = writer.as_mut() {
+                            writer.write(output_point.point).expect("could not write las point");
+                        }
+                    }
+                    bench.lap(Stage::Writing);
+                }
+                if let Some(writer) = writer.as_mut() {
+                    writer.close().expect("could not close las writer");
+                }
+                if let Some(rgb_writer) = rgb_writer.as_mut() {
+                    rgb_writer.close().expect("could not close las writer");
+                }
+                for split_writer in &mut split_writers {
+                    split_writer.close().expect("could not close las writer");
+                }
+                bench
+            });
+
+            // Projects the points into the images, on this thread.
+            //
+            // Each chunk is transformed as a whole, so the transforms run over plain arrays.
+            let socs_to_glcs = Affine::from_fn(|x, y, z| {
+                let glcs = Point::socs(x, y, z)
+                    .to_prcs(scan_position.sop)
+                    .to_glcs(self.project.pop);
+                (glcs.x, glcs.y, glcs.z)
+            });
+            // Reused for every chunk and point, so projecting doesn't allocate.
+            let mut socs_batch = Batch::default();
+            let mut glcs_batch = Batch::default();
+            let mut cmcs_batches = vec![Batch::default(); image_groups.len()];
+            let mut in_frustums = vec![Vec::new(); image_groups.len()];
+            let mut cmcss = Vec::with_capacity(image_groups.len());
+            let mut pixels = Vec::with_capacity(image_groups.len());
+            let chunks = Some(first_chunk).into_iter().chain(read_receiver.iter());
+            for chunk in chunks {
+                let mut output_points = Vec::with_capacity(chunk.len());
+                bench.start();
+                socs_batch.clear();
+                socs_batch.extend(
+                    chunk
+                        .iter()
+                        .map(|point| (point.socs.x, point.socs.y, point.socs.z)),
+                );
+                socs_to_glcs.apply(&socs_batch, &mut glcs_batch);
+                bench.lap(Stage::Coordinates);
+                // Points outside of an image's frustum aren't projected, like in
+                // `ImageGroup::pixel`.
+                for ((image_group, cmcs_batch), in_frustum) in image_groups
+                    .iter()
+                    .zip(cmcs_batches.iter_mut())
+                    .zip(in_frustums.iter_mut())
+                {
+                    image_group.frustum.contains_batch(&socs_batch, in_frustum);
+                    image_group.socs_to_cmcs.apply(&socs_batch, cmcs_batch);
+                }
+                bench.lap(Stage::Projection);
+                for (i, point) in chunk.into_iter().enumerate() {
+                    bench.start();
+                    counts.read += 1;
+                    if counts.read % PROGRESS_INTERVAL == 0 {
+                        progress.set_position(counts.read);
+                        progress.set_message(&format!("{} written", counts.written));
+                        logger::event(json!({
+                            "event": "points",
+                            "infile": translation.infile.display().to_string(),
+                            "read": counts.read,
+                            "written": counts.written,
+                        }));
+                    }
+                    let socs = point.socs;
+                    let (x, y, z) = glcs_batch.get(i);
+                    let glcs = Point::glcs(x, y, z);
+                    cmcss.clear();
+                    cmcss.extend(cmcs_batches.iter().map(|cmcs_batch| {
+                        let (x, y, z) = cmcs_batch.get(i);
+                        Point::cmcs(x, y, z)
+                    }));
+                    bench.lap(Stage::Projection);
+                    pixels.clear();
+                    pixels.extend(
+                        image_groups
+                            .iter()
+                            .zip(cmcss.iter())
+                            .zip(in_frustums.iter())
+                            .map(|((image_group, cmcs), in_frustum)| if in_frustum[i] {
+                                image_group.cmcs_pixel(cmcs)
+                            } else {
+                                None
+                            }),
+                    );
+                    bench.lap(Stage::Projection);
+                    for ((image_group, coverage), pixel) in
+                        image_groups.iter().zip(coverages.iter_mut()).zip(pixels.iter())
+                    {
+                        if let Some((x, y)) = *pixel {
+                            let (width, height) = image_group.file_dimensions();
+                            if x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height {
+                                coverage[y as usize * width + x as usize] += 1;
+                            }
+                        }
+                    }
+                    for (((image_group, rejections), pixel), cmcs) in image_groups
+                        .iter()
+                        .zip(rejections.iter_mut())
+                        .zip(pixels.iter())
+                        .zip(cmcss.iter())
+                    {
+                        rejections.add(cmcs, pixel.is_some(), image_group.camera_calibration);
+                    }
+                    let image_temperatures = image_groups
+                        .iter()
+                        .zip(pixels.iter())
+                        .map(|(image_group, pixel)| {
+                            pixel.and_then(|pixel| image_group.temperature_at(pixel, &socs))
+                        })
+                        .collect::<Vec<_>>();
+                    let temperatures = image_temperatures
+                        .iter()
+                        .filter_map(|&temperature| temperature)
+                        .chain(self.thermal_orthos.iter().filter_map(
+                            |&(_, ref ortho)| ortho.get(glcs.x, glcs.y),
+                        ))
+                        .collect::<Vec<_>>();
+                    bench.lap(Stage::Lookup);
+                    let is_spread = self.max_temperature_spread
+                        .map(|max| spread(&temperatures) > max)
+                        .unwrap_or(false);
+                    let temperature = if temperatures.len() < self.min_images || is_spread {
+                        None
+                    } else {
+                        mean(&temperatures)
+                    };
+                    let temperature = match temperature {
+                        Some(temperature) => temperature,
+                        None if self.keep_without_thermal => f64::NAN,
+                        None => {
+                            counts.add_no_thermal(if temperatures.is_empty() {
+                                no_thermal_reason(&image_groups, &cmcss, &pixels)
+                            } else if is_spread {
+                                NoThermal::Disagreement
+                            } else {
+                                NoThermal::TooFewImages
+                            });
+                            continue;
+                        }
+                    };
+                    let delta = baseline_image_groups.as_ref().map(|baseline_image_groups| {
+                        let baselines = baseline_image_groups
+                            .iter()
+                            .filter_map(|image_group| image_group.temperature(&socs))
+                            .collect::<Vec<_>>();
+                        mean(&baselines)
+                            .map(|baseline| temperature - baseline)
+                            .unwrap_or(f64::NAN)
+                    });
+                    bench.lap(Stage::Lookup);
+                    if delta.map(|delta| delta.is_nan()).unwrap_or(false) &&
+                        !self.keep_without_thermal
+                    {
+                        counts.no_baseline += 1;
+                        continue;
+                    }
+                    let output = self.to_output(&glcs);
+                    footprint.add(output.x, output.y);
+                    if let (Some(ortho), false) = (ortho.as_mut(), temperature.is_nan()) {
+                        ortho.add(output.x, output.y, temperature);
+                    }
+                    let temperature_color = self.to_color(delta.unwrap_or(temperature) as f32);
+                    if let (Some(preview), false) = (preview.as_mut(), temperature.is_nan()) {
+                        preview.add(socs.x, socs.y, socs.z, temperature_color);
+                    }
+                    let rgb_color = mean_color(
+                        &rgb_image_groups
+                            .iter()
+                            .filter_map(|rgb_image_group| rgb_image_group.color(&socs))
+                            .collect::<Vec<_>>(),
+                    ).unwrap_or(temperature_color);
+                    // In the order of `extra_attributes`.
+                    let mut extra_values = Vec::new();
+                    if let Some(delta) = delta {
+                        extra_values.push(delta);
+                    }
+                    if self.image_ids {
+                        let image_id = central_image(&image_groups, &pixels, &image_temperatures);
+                        extra_values.push(image_id as f64);
+                    }
+                    // Points must have exactly the fields of the point format.
+                    let mut point = las::Point {
+                        x: output.x,
+                        y: output.y,
+                        z: output.z,
+                        intensity: point.intensity,
+                        color: if format.has_color {
+                            Some(rgb_color)
+                        } else {
+                            None
+                        },
+                        gps_time: if format.has_gps_time {
+                            Some(self.nodata_value.apply(temperature))
+                        } else {
+                            None
+                        },
+                        nir: if format.has_nir {
+                            Some(self.to_nir(temperature))
+                        } else {
+                            None
+                        },
+                        waveform: if format.has_waveform {
+                            Some(Waveform::default())
+                        } else {
+                            None
+                        },
+                        extra_bytes: extra_attributes.encode(&extra_values),
+                        ..Default::default()
+                    };
+                    if let Some(flag) = self.flag_without_thermal {
+                        if temperature.is_nan() ||
+                            delta.map(|delta| delta.is_nan()).unwrap_or(false)
+                        {
+                            flag.apply(&mut point);
+                        }
+                    }
+                    // With `--dual-output`, the RGB file gets the RGB colors, and the thermal file
+                    // gets the temperature colors.
+                    let (point, rgb_point) = if self.dual_output {
+                        let thermal_point = las::Point {
+                            color: point.color.map(|_| temperature_color),
+                            ..point.clone()
+                        };
+                        (thermal_point, Some(point))
+                    } else {
+                        (point, None)
+                    };
+                    output_points.push(OutputPoint {
+                        point: point,
+                        rgb_point: rgb_point,
+                        split_index: self.split_index(delta.unwrap_or(temperature)),
+                    });
+                    bench.lap(Stage::Transform);
+                    counts.written += 1;
+                    histogram.add(delta.unwrap_or(temperature));
+                    range.0.add(delta.unwrap_or(temperature));
+                    range.1.add(delta.unwrap_or(temperature));
+                }
+                if write_sender.send(output_points).is_err() {
+                    // The writer panicked, which we'll hear about when joining it.
+                    break;
+                }
+            }
+            // Lets the writer finish once it's written everything, and stops the reader if we
+            // stopped early.
+            drop(write_sender);
+            drop(read_receiver);
+            match writing.join() {
+                Ok(writing_bench) => bench.merge(&writing_bench),
+                Err(payload) => panic::resume_unwind(payload),
+            }
+            match reading.join() {
+                Ok((reading_bench, read_error, filtered)) => {
+                    bench.merge(&reading_bench);
+                    counts.filtered = filtered;
+                    read_error
+                }
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        });
+        progress.finish_and_clear();
+        info!("    - {}", counts);
+        if let (Some(min), Some(max)) = (range.0.estimate(), range.1.estimate()) {
+            info!(
+                "    - {:.0}% to {:.0}% quantile temperatures: {:.1} to {:.1}",
+                RANGE_QUANTILES.0 * 100.,
+                RANGE_QUANTILES.1 * 100.,
+                min,
+                max
+            );
+            logger::event(json!({
+                "event": "temperature_range",
+                "infile": translation.infile.display().to_string(),
+                "min": min,
+                "max": max,
+            }));
+        }
+        if self.timings {
+            info!("    - timings: {}", bench.timings());
+            logger::event(json!({
+                "event": "timings",
+                "infile": translation.infile.display().to_string(),
+                "seconds": bench
+                    .seconds()
+                    .into_iter()
+                    .map(|(name, seconds)| (name.to_string(), json!(seconds)))
+                    .collect::<serde_json::Map<_, _>>(),
+            }));
+        }
+        if self.bench {
+            info!(
+                "    - {}",
+                bench.report(counts.read, counts.read, counts.written)
+            );
+            return Colorization {
+                checksums: Vec::new(),
+                counts: counts,
+                footprint: Footprint::default(),
+                histogram: histogram,
+                ortho: None,
+                preview: None,
+                read_error: read_error,
+            };
+        }
+        logger::event(json!({
+            "event": "translation_finished",
+            "infile": translation.infile.display().to_string(),
+            "outfile": translation.outfile.display().to_string(),
+            "read": counts.read,
+            "written": counts.written,
+        }));
+        for (image_group, coverage) in image_groups.iter().zip(coverages.iter()) {
+            let path = image_group.irb_path.with_file_name(format!(
+                "{}-coverage.png",
+                self.image_stem(&image_group.irb_path)
+            ));
+            image_group.write_coverage(&path, coverage);
+        }
+        for (image_group, rejections) in image_groups.iter().zip(rejections.iter()) {
+            let path = image_group.irb_path.with_file_name(format!(
+                "{}-rejections.png",
+                self.image_stem(&image_group.irb_path)
+            ));
+            info!(
+                "    - {}: {} accepted, {} behind the camera, {} outside the angle mask, {} \
+                 outside the image",
+                image_group.irb_path.display(),
+                rejections.accepted,
+                rejections.behind_camera,
+                rejections.angle_mask,
+                rejections.bounds
+            );
+            logger::event(json!({
+                "event": "rejections",
+                "path": image_group.irb_path.display().to_string(),
+                "accepted": rejections.accepted,
+                "behind_camera": rejections.behind_camera,
+                "angle_mask": rejections.angle_mask,
+                "bounds": rejections.bounds,
+            }));
+            rejections.write_to_path(&path).expect(&format!(
+                "Could not write rejections {}",
+                path.display()
+            ));
+        }
+        let mut outputs = vec![translation.outfile.clone()];
+        outputs.extend(translation.rgb_outfile.clone());
+        outputs.extend(translation.split_outfiles.iter().cloned());
+        if self.laz {
+            for outfile in &outputs {
+                self.compress(outfile);
+            }
+        }
+        let preview = preview.map(|preview| {
+            let path = translation.outfile.with_extension("png");
+            preview.write_to_path(&path).expect(&format!(
+                "Could not write preview {}",
+                path.display()
+            ));
+            outputs.push(path.clone());
+            path
+        });
+        if let (Some(ortho), false) = (ortho.as_ref(), self.merge_ortho) {
+            let path = translation.outfile.with_extension("tif");
+            self.write_ortho(ortho, &path);
+            outputs.push(path);
+        }
+        let checksums = outputs
+            .iter()
+            .filter_map(|path| self.checksum(path))
+            .collect();
+        // Previews are uploaded at the end of the run, after the report embeds them.
+        for path in outputs.iter().filter(|&path| Some(path) != preview.as_ref()) {
+            self.publish(path);
+        }
+        Colorization {
+            checksums: checksums,
+            counts: counts,
+            footprint: footprint,
+            histogram: histogram,
+            ortho: ortho,
+            preview: preview,
+            read_error: read_error,
+        }
+    }
+}
+
+/// Returns why a point didn't get a temperature from any image, given the point in each image's
+/// camera coordinate system and the pixel it projected into, if any.
+fn no_thermal_reason(
+    image_groups: &[ImageGroup],
+    cmcss: &[Point<Cmcs>],
+    pixels: &[Option<(i32, i32)>],
+) -> NoThermal {
+    if image_groups.is_empty() {
+        return NoThermal::NoImages;
+    }
+    let is_in_an_image = image_groups.iter().zip(pixels.iter()).any(|(image_group, pixel)| {
+        match *pixel {
+            Some((x, y)) => {
+                let (width, height) = image_group.file_dimensions();
+                x >= 0 && y >= 0 && (x as usize) < width && (y as usize) < height
+            }
+            None => false,
+        }
+    });
+    if is_in_an_image {
+        NoThermal::InvalidPixels
+    } else if cmcss.iter().all(|cmcs| cmcs.is_behind_camera()) {
+        NoThermal::BehindCamera
+    } else {
+        NoThermal::OutsideImages
+    }
+}
+
+/// Returns the one-based id of the image, among those with a temperature for this point, that the
+/// point projects nearest to the center of, or zero if there isn't one.
+///
+/// Images are averaged, so this is the image that would win if only the least-distorted image were
+/// used.
+fn central_image(
+    image_groups: &[ImageGroup],
+    pixels: &[Option<(i32, i32)>],
+    temperatures: &[Option<f64>],
+) -> usize {
+    image_groups
+        .iter()
+        .zip(pixels.iter().zip(temperatures.iter()))
+        .enumerate()
+        .filter_map(|(i, (image_group, (&pixel, temperature)))| {
+            let (x, y) = pixel?;
+            temperature.map(|_| {
+                let (width, height) = image_group.file_dimensions();
+                let dx = f64::from(x) - width as f64 / 2.;
+                let dy = f64::from(y) - height as f64 / 2.;
+                (i + 1, dx * dx + dy * dy)
+            })
+        })
+        .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+        .map(|(id, _)| id)
+        .unwrap_or(0)
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f64>() / values.len() as f64)
+    }
+}
+
+/// Returns the difference between the largest and smallest values, or zero if there are none.
+fn spread(values: &[f64]) -> f64 {
+    use std::f64;
+
+    if values.is_empty() {
+        return 0.;
+    }
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    max - min
+}
+
+fn mean_color(colors: &[Color]) -> Option<Color> {
+    if colors.is_empty() {
+        return None;
+    }
+    let mean = |channel: fn(&Color) -> u16| {
+        (colors.iter().map(|color| u64::from(channel(color))).sum::<u64>() /
+             colors.len() as u64) as u16
+    };
+    Some(Color {
+        red: mean(|color| color.red),
+        green: mean(|color| color.green),
+        blue: mean(|color| color.blue),
+    })
+}
@@ -0,0 +1,80 @@
+//! Thermal images decoded into memory or into memory-mapped files, for fast sampling.
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use memmap::Mmap;
+use std::f32;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Makes the names of the mapped files unique within this process.
+static MAPPED_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// The temperatures of every pixel of an image, row by row.
+///
+/// NaN marks pixels that couldn't be read.
+pub enum Decoded {
+    /// On the heap.
+    Memory(Vec<f32>),
+    /// In a temporary file that's mapped into memory, so the operating system decides which parts
+    /// stay resident.
+    Mapped(MappedFile),
+}
+
+/// A temporary file of little-endian floats that's mapped into memory, and removed when dropped.
+pub struct MappedFile {
+    map: Option<Mmap>,
+    path: PathBuf,
+}
+
+impl Decoded {
+    /// Writes the temperatures to a temporary file and maps it into memory.
+    pub fn mapped<I: Iterator<Item = f32>>(temperatures: I) -> io::Result<Decoded> {
+        let mut path = ::std::env::temp_dir();
+        path.push(format!(
+            "tce-{}-{}.f32",
+            process::id(),
+            MAPPED_FILES.fetch_add(1, Ordering::SeqCst)
+        ));
+        let mut mapped_file = MappedFile {
+            map: None,
+            path: path,
+        };
+        {
+            let mut writer = BufWriter::new(File::create(&mapped_file.path)?);
+            for temperature in temperatures {
+                writer.write_f32::<LittleEndian>(temperature)?;
+            }
+            writer.flush()?;
+        }
+        let file = File::open(&mapped_file.path)?;
+        // The file is ours, and nobody else writes to it while it's mapped.
+        mapped_file.map = Some(unsafe { Mmap::map(&file)? });
+        Ok(Decoded::Mapped(mapped_file))
+    }
+
+    /// Returns the temperature of the i-th pixel, or NaN if it's out of bounds.
+    pub fn get(&self, i: usize) -> f32 {
+        match *self {
+            Decoded::Memory(ref temperatures) => temperatures.get(i).cloned().unwrap_or(f32::NAN),
+            Decoded::Mapped(ref mapped_file) => {
+                let bytes = mapped_file.map.as_ref().map(|map| &map[..]).unwrap_or(&[]);
+                if (i + 1) * 4 > bytes.len() {
+                    f32::NAN
+                } else {
+                    LittleEndian::read_f32(&bytes[i * 4..(i + 1) * 4])
+                }
+            }
+        }
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        // Unmap first, since some platforms can't remove a mapped file.
+        self.map.take();
+        let _ = fs::remove_file(&self.path);
+    }
+}
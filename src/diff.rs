@@ -0,0 +1,143 @@
+use clap::ArgMatches;
+use extra_bytes::{Attribute, Registry};
+use las::{self, Color};
+use las::point::Format;
+use palette::{Gradient, Rgb};
+use std::collections::HashMap;
+use std::u16;
+
+/// A colorized point from the earlier epoch.
+struct Before {
+    x: f64,
+    y: f64,
+    z: f64,
+    temperature: f64,
+}
+
+/// Colors the points of one colorized las file by their temperature change from the nearest point
+/// in another.
+///
+/// Temperatures are read from the gps time field, as written by the colorizer. Points that don't
+/// have a neighbor within the tolerance, or that lack a temperature, are dropped.
+pub fn diff(matches: &ArgMatches) {
+    let tolerance = value_t!(matches, "tolerance", f64).unwrap();
+    let min_delta = value_t!(matches, "min-delta", f32).unwrap();
+    let max_delta = value_t!(matches, "max-delta", f32).unwrap();
+    let gradient = Gradient::with_domain(vec![
+        (min_delta, Rgb::new(0., 0., 1.)),
+        (0., Rgb::new(1., 1., 1.)),
+        (max_delta, Rgb::new(1., 0., 0.)),
+    ]);
+
+    let before_path = matches.value_of("BEFORE").unwrap();
+    info!("Reading {}...", before_path);
+    let mut cells: HashMap<(i64, i64, i64), Vec<Before>> = HashMap::new();
+    let mut reader = las::Reader::from_path(before_path).unwrap();
+    for point in reader.points() {
+        let point = point.expect("could not read las point");
+        let temperature = match point.gps_time {
+            Some(temperature) if !temperature.is_nan() => temperature,
+            _ => continue,
+        };
+        cells
+            .entry(cell(point.x, point.y, point.z, tolerance))
+            .or_insert_with(Vec::new)
+            .push(Before {
+                x: point.x,
+                y: point.y,
+                z: point.z,
+                temperature: temperature,
+            });
+    }
+
+    let after_path = matches.value_of("AFTER").unwrap();
+    let outfile = matches.value_of("OUTFILE").unwrap();
+    info!("Comparing {} and writing {}...", after_path, outfile);
+    let mut reader = las::Reader::from_path(after_path).unwrap();
+    let mut attributes = Registry::new();
+    attributes.add(Attribute::new("delta temperature", "Temperature minus earlier").units("°C"));
+    let mut header = las::Header::default();
+    header.point_format = Format::new(3).unwrap();
+    header.transforms = reader.header().transforms;
+    attributes.apply(&mut header);
+    let mut writer = las::Writer::from_path(outfile, header).unwrap();
+    let (mut matched, mut unmatched) = (0, 0);
+    for point in reader.points() {
+        let point = point.expect("could not read las point");
+        let temperature = match point.gps_time {
+            Some(temperature) if !temperature.is_nan() => temperature,
+            _ => continue,
+        };
+        let before = match nearest(&cells, point.x, point.y, point.z, tolerance) {
+            Some(before) => before,
+            None => {
+                unmatched += 1;
+                continue;
+            }
+        };
+        let delta = temperature - before.temperature;
+        let color = gradient.get(delta as f32);
+        writer
+            .write(las::Point {
+                x: point.x,
+                y: point.y,
+                z: point.z,
+                intensity: point.intensity,
+                color: Some(Color {
+                    red: (u16::MAX as f32 * color.red) as u16,
+                    green: (u16::MAX as f32 * color.green) as u16,
+                    blue: (u16::MAX as f32 * color.blue) as u16,
+                }),
+                gps_time: Some(temperature),
+                extra_bytes: attributes.encode(&[delta]),
+                ..Default::default()
+            })
+            .expect("could not write las point");
+        matched += 1;
+    }
+    info!(
+        "Done, {} points matched and {} points had no neighbor within {}m.",
+        matched,
+        unmatched,
+        tolerance
+    );
+}
+
+fn cell(x: f64, y: f64, z: f64, size: f64) -> (i64, i64, i64) {
+    (
+        (x / size).floor() as i64,
+        (y / size).floor() as i64,
+        (z / size).floor() as i64,
+    )
+}
+
+/// Returns the closest earlier point within the tolerance, searching the neighboring cells.
+fn nearest<'a>(
+    cells: &'a HashMap<(i64, i64, i64), Vec<Before>>,
+    x: f64,
+    y: f64,
+    z: f64,
+    tolerance: f64,
+) -> Option<&'a Before> {
+    let (i, j, k) = cell(x, y, z, tolerance);
+    let mut nearest = None;
+    let mut nearest_distance2 = tolerance.powi(2);
+    for di in -1..2 {
+        for dj in -1..2 {
+            for dk in -1..2 {
+                for before in cells.get(&(i + di, j + dj, k + dk)).into_iter().flat_map(
+                    |points| points,
+                )
+                {
+                    let distance2 = (before.x - x).powi(2) + (before.y - y).powi(2) +
+                        (before.z - z).powi(2);
+                    if distance2 <= nearest_distance2 {
+                        nearest = Some(before);
+                        nearest_distance2 = distance2;
+                    }
+                }
+            }
+        }
+    }
+    nearest
+}
@@ -0,0 +1,59 @@
+use riscan_pro::CameraCalibration;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use toml;
+
+/// Brown–Conrady lens distortion that is applied on top of a camera calibration, for lenses whose
+/// distortion RiSCAN Pro doesn't capture well.
+///
+/// Coefficients are in the camera calibration's normalized image coordinates, i.e. pixels relative
+/// to `(cx, cy)` and divided by `(fx, fy)`.
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+pub struct Distortion {
+    #[serde(default)]
+    pub k1: f64,
+    #[serde(default)]
+    pub k2: f64,
+    #[serde(default)]
+    pub k3: f64,
+    #[serde(default)]
+    pub p1: f64,
+    #[serde(default)]
+    pub p2: f64,
+}
+
+impl Distortion {
+    /// Reads coefficients from a toml file, e.g. `k1 = -0.12`. Missing coefficients are zero.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Distortion> {
+        let mut s = String::new();
+        File::open(path)?.read_to_string(&mut s)?;
+        toml::from_str(&s).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Distorts a pixel from the camera calibration.
+    ///
+    /// Returns `None` if the distorted pixel is outside of the image.
+    pub fn apply(
+        &self,
+        u: f64,
+        v: f64,
+        camera_calibration: &CameraCalibration,
+    ) -> Option<(f64, f64)> {
+        let x = (u - camera_calibration.cx) / camera_calibration.fx;
+        let y = (v - camera_calibration.cy) / camera_calibration.fy;
+        let r2 = x * x + y * y;
+        let radial = 1. + self.k1 * r2 + self.k2 * r2 * r2 + self.k3 * r2 * r2 * r2;
+        let xd = x * radial + 2. * self.p1 * x * y + self.p2 * (r2 + 2. * x * x);
+        let yd = y * radial + self.p1 * (r2 + 2. * y * y) + 2. * self.p2 * x * y;
+        let u = xd * camera_calibration.fx + camera_calibration.cx;
+        let v = yd * camera_calibration.fy + camera_calibration.cy;
+        if u < 0. || v < 0. || u >= camera_calibration.width as f64 ||
+            v >= camera_calibration.height as f64
+        {
+            None
+        } else {
+            Some((u, v))
+        }
+    }
+}
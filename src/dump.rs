@@ -0,0 +1,67 @@
+//! Saves the resolved command line options, for reproducing a run.
+
+use clap::ArgMatches;
+use serde_json::{self, Map, Value};
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use toml;
+use yaml_rust::Yaml;
+
+/// Returns the value of every top-level option in the cli definition, as resolved by clap.
+///
+/// Values come from the command line, the `TCE_*` environment variables, or the defaults. Options
+/// without a value are left out, flags are booleans (or counts, if they can be repeated), and
+/// options that can be repeated are arrays.
+pub fn options(yaml: &Yaml, matches: &ArgMatches) -> Value {
+    let mut options = Map::new();
+    options.insert(
+        "tce_version".to_string(),
+        Value::String(env!("CARGO_PKG_VERSION").to_string()),
+    );
+    for arg in yaml["args"].as_vec().expect("cli.yml has no args") {
+        let (name, settings) = arg.as_hash()
+            .and_then(|hash| hash.iter().next())
+            .expect("invalid arg in cli.yml");
+        let name = name.as_str().expect("invalid arg name in cli.yml");
+        let takes_value = settings["takes_value"].as_bool().unwrap_or(false) ||
+            !settings["index"].is_badvalue();
+        let multiple = settings["multiple"].as_bool().unwrap_or(false);
+        let value = if takes_value {
+            match matches.values_of(name) {
+                Some(values) => {
+                    let values = values
+                        .map(|value| Value::String(value.to_string()))
+                        .collect::<Vec<_>>();
+                    if multiple {
+                        Value::Array(values)
+                    } else {
+                        values.into_iter().next().unwrap()
+                    }
+                }
+                None => continue,
+            }
+        } else if multiple {
+            Value::from(matches.occurrences_of(name))
+        } else {
+            Value::Bool(matches.is_present(name))
+        };
+        options.insert(name.to_string(), value);
+    }
+    Value::Object(options)
+}
+
+/// Writes options to a path, as TOML if the extension is `toml` and as JSON otherwise.
+pub fn write(path: &Path, options: &Value) {
+    let mut file = File::create(path).expect(&format!("Could not create {}", path.display()));
+    if path.extension().map(|extension| extension == "toml").unwrap_or(false) {
+        let options = toml::Value::try_from(options).expect("Could not convert options to TOML");
+        let s = toml::to_string(&options).expect("Could not serialize options as TOML");
+        file.write_all(s.as_bytes()).expect(&format!("Could not write {}", path.display()));
+    } else {
+        serde_json::to_writer_pretty(file, options).expect(&format!(
+            "Could not write {}",
+            path.display()
+        ));
+    }
+}
@@ -0,0 +1,166 @@
+//! Writes the georeferenced outputs: the GeoJSON footprints, orthomosaics, and KML camera poses.
+
+use Config;
+use footprint::Footprint;
+use ortho::Ortho;
+use riscan_pro::{Point, ScanPosition};
+use serde_json;
+use std::io::Write;
+use std::path::Path;
+
+impl Config {
+    /// Writes each scan position's origin and the footprint of its colorized points as a GeoJSON
+    /// feature collection.
+    ///
+    /// Coordinates are in the project's global coordinate system, or the `--transform`ed one.
+    pub fn write_geojson(&self, path: &Path, footprints: &[(&ScanPosition, Footprint)]) {
+        use std::fs::File;
+
+        let mut features = Vec::new();
+        for &(scan_position, ref footprint) in footprints {
+            let origin = Point::socs(0., 0., 0.)
+                .to_prcs(scan_position.sop)
+                .to_glcs(self.project.pop);
+            let origin = self.to_output(&origin);
+            features.push(json!({
+                "type": "Feature",
+                "geometry": {
+                    "type": "Point",
+                    "coordinates": [origin.x, origin.y, origin.z],
+                },
+                "properties": {
+                    "scan_position": scan_position.name,
+                    "kind": "origin",
+                },
+            }));
+            let hull = footprint.convex_hull();
+            if hull.is_empty() {
+                continue;
+            }
+            let ring = hull.iter().map(|&(x, y)| vec![x, y]).collect::<Vec<_>>();
+            features.push(json!({
+                "type": "Feature",
+                "bbox": footprint.bounds(),
+                "geometry": {
+                    "type": "Polygon",
+                    "coordinates": [ring],
+                },
+                "properties": {
+                    "scan_position": scan_position.name,
+                    "kind": "footprint",
+                    "points": footprint.count(),
+                },
+            }));
+        }
+        let feature_collection = json!({
+            "type": "FeatureCollection",
+            "features": features,
+        });
+        let file = File::create(path).expect(&format!("Could not create {}", path.display()));
+        serde_json::to_writer_pretty(file, &feature_collection).expect(&format!(
+            "Could not write {}",
+            path.display()
+        ));
+    }
+
+    pub fn write_ortho(&self, ortho: &Ortho, path: &Path) {
+        if ortho.is_empty() {
+            warn!("no thermal points for {}, skipping", path.display());
+            return;
+        }
+        ortho
+            .to_geotiff(self.ortho_statistic)
+            .write_to_path(path)
+            .expect(&format!("Could not write orthomosaic {}", path.display()));
+    }
+
+    /// Writes the scan position origins and each image's camera pose as KML placemarks.
+    ///
+    /// The project's global coordinate system must be the `--utm-zone`, and the camera poses use
+    /// KML's heading (clockwise from north) and tilt (zero is straight down) conventions.
+    pub fn write_kml(&self, path: &Path) {
+        use std::fs::File;
+        use std::io::BufWriter;
+
+        let utm_zone = self.utm_zone.unwrap();
+        let mut kml = String::new();
+        kml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        kml.push_str("<kml xmlns=\"http://www.opengis.net/kml/2.2\">\n<Document>\n");
+        kml.push_str(&format!(
+            "<name>{}</name>\n",
+            xml_escape(&self.project.path.to_string_lossy())
+        ));
+        for scan_position in self.scan_positions() {
+            let origin = Point::socs(0., 0., 0.)
+                .to_prcs(scan_position.sop)
+                .to_glcs(self.project.pop);
+            let (longitude, latitude) = utm_zone.to_wgs84(origin.x, origin.y);
+            kml.push_str(&format!("<Folder>\n<name>{}</name>\n", xml_escape(&scan_position.name)));
+            kml.push_str(&format!(
+                "<Placemark>\n<name>{}</name>\n<Point><altitudeMode>absolute</altitudeMode>\
+                 <coordinates>{},{},{}</coordinates></Point>\n</Placemark>\n",
+                xml_escape(&scan_position.name),
+                longitude,
+                latitude,
+                origin.z
+            ));
+            // Only the selected cameras' calibrations are checked up front.
+            let mut images = scan_position
+                .images
+                .values()
+                .filter(|image| self.is_selected_camera(image))
+                .collect::<Vec<_>>();
+            images.sort_by_key(|image| &image.name);
+            for image in images {
+                let mount_calibration = self.mount_calibration(Path::new(&image.name), image);
+                let to_glcs = |x, y, z| {
+                    Point::cmcs(x, y, z)
+                        .to_socs(image.cop, mount_calibration)
+                        .to_prcs(scan_position.sop)
+                        .to_glcs(self.project.pop)
+                };
+                let position = to_glcs(0., 0., 0.);
+                let forward = to_glcs(0., 0., 1.);
+                let (dx, dy, dz) = (
+                    forward.x - position.x,
+                    forward.y - position.y,
+                    forward.z - position.z,
+                );
+                let heading = dx.atan2(dy).to_degrees();
+                let elevation = (dz / (dx.powi(2) + dy.powi(2) + dz.powi(2)).sqrt()).asin();
+                let tilt = 90. + elevation.to_degrees();
+                let (longitude, latitude) = utm_zone.to_wgs84(position.x, position.y);
+                kml.push_str(&format!(
+                    "<Placemark>\n<name>{}</name>\n<Camera><longitude>{}</longitude>\
+                     <latitude>{}</latitude><altitude>{}</altitude><heading>{}</heading>\
+                     <tilt>{}</tilt><roll>0</roll><altitudeMode>absolute</altitudeMode></Camera>\n\
+                     <Point><altitudeMode>absolute</altitudeMode>\
+                     <coordinates>{},{},{}</coordinates></Point>\n</Placemark>\n",
+                    xml_escape(&image.name),
+                    longitude,
+                    latitude,
+                    position.z,
+                    heading,
+                    tilt,
+                    longitude,
+                    latitude,
+                    position.z
+                ));
+            }
+            kml.push_str("</Folder>\n");
+        }
+        kml.push_str("</Document>\n</kml>\n");
+        let mut file = BufWriter::new(File::create(path).expect(&format!(
+            "Could not create {}",
+            path.display()
+        )));
+        file.write_all(kml.as_bytes()).expect(&format!(
+            "Could not write {}",
+            path.display()
+        ));
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
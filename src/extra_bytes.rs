@@ -0,0 +1,250 @@
+use byteorder::{LittleEndian, WriteBytesExt};
+use las::{Header, Vlr};
+
+/// The extra bytes descriptor option bits for the fields we fill in.
+const NO_DATA_BIT: u8 = 1;
+const SCALE_BIT: u8 = 1 << 3;
+const OFFSET_BIT: u8 = 1 << 4;
+
+/// How an attribute's values are stored in the extra bytes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DataType {
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+}
+
+/// A per-point value stored in the las point's extra bytes.
+///
+/// Values are given as doubles and converted to the data type when they're encoded, after
+/// subtracting the offset and dividing by the scale. NaN values are stored as the no-data value,
+/// if there is one.
+#[derive(Clone, Debug)]
+pub struct Attribute {
+    pub name: String,
+    pub description: String,
+    pub data_type: DataType,
+    /// Appended to the description, since the descriptor has no field for them.
+    pub units: Option<String>,
+    pub no_data: Option<f64>,
+    pub scale: Option<f64>,
+    pub offset: Option<f64>,
+}
+
+/// The attributes stored in each point's extra bytes, in order.
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    attributes: Vec<Attribute>,
+}
+
+impl DataType {
+    /// Returns the data type's code in the extra bytes descriptor.
+    fn code(&self) -> u8 {
+        match *self {
+            DataType::U8 => 1,
+            DataType::I8 => 2,
+            DataType::U16 => 3,
+            DataType::I16 => 4,
+            DataType::U32 => 5,
+            DataType::I32 => 6,
+            DataType::U64 => 7,
+            DataType::I64 => 8,
+            DataType::F32 => 9,
+            DataType::F64 => 10,
+        }
+    }
+
+    /// Returns the number of bytes of one value.
+    fn len(&self) -> usize {
+        match *self {
+            DataType::U8 | DataType::I8 => 1,
+            DataType::U16 | DataType::I16 => 2,
+            DataType::U32 | DataType::I32 | DataType::F32 => 4,
+            DataType::U64 | DataType::I64 | DataType::F64 => 8,
+        }
+    }
+
+    /// Writes a raw value, rounding it for integer types.
+    fn write(&self, value: f64, bytes: &mut Vec<u8>) {
+        match *self {
+            DataType::U8 => bytes.write_u8(value.round() as u8),
+            DataType::I8 => bytes.write_i8(value.round() as i8),
+            DataType::U16 => bytes.write_u16::<LittleEndian>(value.round() as u16),
+            DataType::I16 => bytes.write_i16::<LittleEndian>(value.round() as i16),
+            DataType::U32 => bytes.write_u32::<LittleEndian>(value.round() as u32),
+            DataType::I32 => bytes.write_i32::<LittleEndian>(value.round() as i32),
+            DataType::U64 => bytes.write_u64::<LittleEndian>(value.round() as u64),
+            DataType::I64 => bytes.write_i64::<LittleEndian>(value.round() as i64),
+            DataType::F32 => bytes.write_f32::<LittleEndian>(value as f32),
+            DataType::F64 => bytes.write_f64::<LittleEndian>(value),
+        }.unwrap();
+    }
+
+    /// Writes a descriptor field, which is eight bytes of the widest type of the same kind.
+    fn write_descriptor_field(&self, value: f64, bytes: &mut Vec<u8>) {
+        match *self {
+            DataType::U8 | DataType::U16 | DataType::U32 | DataType::U64 => {
+                bytes.write_u64::<LittleEndian>(value.round() as u64)
+            }
+            DataType::I8 | DataType::I16 | DataType::I32 | DataType::I64 => {
+                bytes.write_i64::<LittleEndian>(value.round() as i64)
+            }
+            DataType::F32 | DataType::F64 => bytes.write_f64::<LittleEndian>(value),
+        }.unwrap();
+    }
+}
+
+impl Attribute {
+    /// Creates a new attribute of doubles, without units or a no-data value.
+    pub fn new(name: &str, description: &str) -> Attribute {
+        Attribute {
+            name: name.to_string(),
+            description: description.to_string(),
+            data_type: DataType::F64,
+            units: None,
+            no_data: None,
+            scale: None,
+            offset: None,
+        }
+    }
+
+    /// Stores the values as another data type.
+    pub fn data_type(mut self, data_type: DataType) -> Attribute {
+        self.data_type = data_type;
+        self
+    }
+
+    /// Sets the units of the values, e.g. "°C".
+    pub fn units(mut self, units: &str) -> Attribute {
+        self.units = Some(units.to_string());
+        self
+    }
+
+    /// Sets the value that's stored for NaN.
+    pub fn no_data(mut self, no_data: f64) -> Attribute {
+        self.no_data = Some(no_data);
+        self
+    }
+
+    /// Stores `(value - offset) / scale`, e.g. to keep a fixed precision in an integer type.
+    pub fn scaled(mut self, scale: f64, offset: f64) -> Attribute {
+        self.scale = Some(scale);
+        self.offset = Some(offset);
+        self
+    }
+
+    /// Returns the value as stored, before it's converted to the data type.
+    fn raw(&self, value: f64) -> f64 {
+        (value - self.offset.unwrap_or(0.)) / self.scale.unwrap_or(1.)
+    }
+
+    /// Writes the attribute's 192-byte extra bytes descriptor.
+    fn write_descriptor(&self, data: &mut Vec<u8>) {
+        let mut options = 0;
+        if self.no_data.is_some() {
+            options |= NO_DATA_BIT;
+        }
+        if self.scale.is_some() {
+            options |= SCALE_BIT;
+        }
+        if self.offset.is_some() {
+            options |= OFFSET_BIT;
+        }
+        data.extend_from_slice(&[0, 0, self.data_type.code(), options]);
+        data.extend_from_slice(&padded(&self.name, 32));
+        data.extend_from_slice(&[0; 4]);
+        match self.no_data {
+            Some(no_data) => {
+                self.data_type.write_descriptor_field(self.raw(no_data), data);
+                data.extend_from_slice(&[0; 16]);
+            }
+            None => data.extend_from_slice(&[0; 24]),
+        }
+        // Min and max, which we don't track.
+        data.extend_from_slice(&[0; 24 * 2]);
+        for field in &[self.scale, self.offset] {
+            data.write_f64::<LittleEndian>(field.unwrap_or(0.)).unwrap();
+            data.extend_from_slice(&[0; 16]);
+        }
+        let description = match self.units {
+            Some(ref units) => format!("{} ({})", self.description, units),
+            None => self.description.clone(),
+        };
+        data.extend_from_slice(&padded(&description, 32));
+    }
+}
+
+impl Registry {
+    /// Creates an empty registry.
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// Adds an attribute after the others.
+    pub fn add(&mut self, attribute: Attribute) {
+        self.attributes.push(attribute);
+    }
+
+    /// Returns true if there are no attributes.
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty()
+    }
+
+    /// Returns the number of extra bytes needed for these attributes.
+    pub fn len(&self) -> u16 {
+        self.attributes
+            .iter()
+            .map(|attribute| attribute.data_type.len())
+            .sum::<usize>() as u16
+    }
+
+    /// Returns the LASF_Spec extra bytes vlr that describes these attributes.
+    pub fn vlr(&self) -> Vlr {
+        let mut data = Vec::with_capacity(self.attributes.len() * 192);
+        for attribute in &self.attributes {
+            attribute.write_descriptor(&mut data);
+        }
+        Vlr {
+            user_id: "LASF_Spec".to_string(),
+            record_id: 4,
+            description: "Extra bytes".to_string(),
+            data: data,
+        }
+    }
+
+    /// Adds the attributes to a header, if there are any.
+    pub fn apply(&self, header: &mut Header) {
+        if self.is_empty() {
+            return;
+        }
+        header.point_format.extra_bytes = self.len();
+        header.vlrs.push(self.vlr());
+    }
+
+    /// Encodes one value per attribute, in order, into a point's extra bytes.
+    pub fn encode(&self, values: &[f64]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(self.len() as usize);
+        for (attribute, &value) in self.attributes.iter().zip(values.iter()) {
+            let value = match attribute.no_data {
+                Some(no_data) if value.is_nan() => no_data,
+                _ => value,
+            };
+            attribute.data_type.write(attribute.raw(value), &mut bytes);
+        }
+        bytes
+    }
+}
+
+fn padded(s: &str, len: usize) -> Vec<u8> {
+    let mut bytes = s.as_bytes().to_vec();
+    bytes.truncate(len);
+    bytes.resize(len, 0);
+    bytes
+}
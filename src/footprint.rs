@@ -0,0 +1,92 @@
+use std::collections::HashSet;
+
+/// The size of the cells that points are binned into, in meters.
+///
+/// The footprint's hull is built from the corners of the occupied cells, so it can overshoot the
+/// points by up to this much.
+const CELL_SIZE: f64 = 1.;
+
+/// The two-dimensional extent of a set of points.
+#[derive(Clone, Debug, Default)]
+pub struct Footprint {
+    cells: HashSet<(i64, i64)>,
+    count: u64,
+}
+
+impl Footprint {
+    /// Adds a point.
+    pub fn add(&mut self, x: f64, y: f64) {
+        self.cells.insert((
+            (x / CELL_SIZE).floor() as i64,
+            (y / CELL_SIZE).floor() as i64,
+        ));
+        self.count += 1;
+    }
+
+    /// Adds all of the other footprint's points to this one.
+    pub fn merge(&mut self, other: &Footprint) {
+        self.cells.extend(other.cells.iter().cloned());
+        self.count += other.count;
+    }
+
+    /// Returns the number of points in this footprint.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Returns the `[min x, min y, max x, max y]` bounding box, or `None` if there are no points.
+    pub fn bounds(&self) -> Option<[f64; 4]> {
+        if self.cells.is_empty() {
+            return None;
+        }
+        let min_x = self.cells.iter().map(|cell| cell.0).min().unwrap();
+        let min_y = self.cells.iter().map(|cell| cell.1).min().unwrap();
+        let max_x = self.cells.iter().map(|cell| cell.0).max().unwrap() + 1;
+        let max_y = self.cells.iter().map(|cell| cell.1).max().unwrap() + 1;
+        Some([
+            min_x as f64 * CELL_SIZE,
+            min_y as f64 * CELL_SIZE,
+            max_x as f64 * CELL_SIZE,
+            max_y as f64 * CELL_SIZE,
+        ])
+    }
+
+    /// Returns the convex hull as a closed, counter-clockwise ring.
+    ///
+    /// Uses Andrew's monotone chain on the corners of the occupied cells.
+    pub fn convex_hull(&self) -> Vec<(f64, f64)> {
+        let mut corners = self.cells
+            .iter()
+            .flat_map(|&(x, y)| vec![(x, y), (x + 1, y), (x, y + 1), (x + 1, y + 1)])
+            .collect::<Vec<_>>();
+        corners.sort();
+        corners.dedup();
+        if corners.len() < 3 {
+            return Vec::new();
+        }
+        let cross = |o: (i64, i64), a: (i64, i64), b: (i64, i64)| {
+            (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+        };
+        let mut hull: Vec<(i64, i64)> = Vec::new();
+        for &corner in &corners {
+            while hull.len() >= 2 &&
+                cross(hull[hull.len() - 2], hull[hull.len() - 1], corner) <= 0
+            {
+                hull.pop();
+            }
+            hull.push(corner);
+        }
+        let lower_len = hull.len() + 1;
+        for &corner in corners.iter().rev().skip(1) {
+            while hull.len() >= lower_len &&
+                cross(hull[hull.len() - 2], hull[hull.len() - 1], corner) <= 0
+            {
+                hull.pop();
+            }
+            hull.push(corner);
+        }
+        hull.into_iter()
+            .map(|(x, y)| (x as f64 * CELL_SIZE, y as f64 * CELL_SIZE))
+            .collect()
+    }
+}
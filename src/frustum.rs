@@ -0,0 +1,169 @@
+//! Cheap visibility tests of points against an image's field of view.
+
+use batch::Batch;
+use nalgebra::{Point3, Vector3};
+use riscan_pro::{CameraCalibration, MountCalibration, Point, Socs};
+use riscan_pro::scan_position::Image;
+
+/// How far outside of the frustum, in meters, a point can be and still be kept, so points on its
+/// faces are left to the exact projection.
+const TOLERANCE: f64 = 1e-9;
+
+/// The pyramid that an image sees, in the scanner's own coordinate system.
+///
+/// This is the camera calibration's angle mask, i.e. the tangents of the angles from the camera's
+/// axis, so every point outside of it would be rejected by the full projection anyway.
+#[derive(Clone, Debug)]
+pub struct Frustum {
+    apex: Point3<f64>,
+    /// The inward-facing unit normals of the four sides.
+    normals: [Vector3<f64>; 4],
+    /// The unit vector along the camera's axis.
+    axis: Vector3<f64>,
+}
+
+impl Frustum {
+    /// Creates the frustum of an image from its calibrations.
+    pub fn new(
+        image: &Image,
+        mount_calibration: &MountCalibration,
+        camera_calibration: &CameraCalibration,
+    ) -> Frustum {
+        Frustum::from_fn(
+            |x, y, z| *Point::cmcs(x, y, z).to_socs(image.cop, mount_calibration),
+            camera_calibration,
+        )
+    }
+
+    /// Creates the frustum of a camera calibration, given the transform from the camera's
+    /// coordinate system to the scanner's own.
+    fn from_fn<F>(to_socs: F, camera_calibration: &CameraCalibration) -> Frustum
+    where
+        F: Fn(f64, f64, f64) -> Point3<f64>,
+    {
+        let apex = to_socs(0., 0., 0.);
+        let axis = (to_socs(0., 0., 1.) - apex).normalize();
+        let corners = [
+            (camera_calibration.tan_min_horz, camera_calibration.tan_min_vert),
+            (camera_calibration.tan_max_horz, camera_calibration.tan_min_vert),
+            (camera_calibration.tan_max_horz, camera_calibration.tan_max_vert),
+            (camera_calibration.tan_min_horz, camera_calibration.tan_max_vert),
+        ];
+        let edges = corners
+            .iter()
+            .map(|&(tan_horz, tan_vert)| to_socs(tan_horz, tan_vert, 1.) - apex)
+            .collect::<Vec<_>>();
+        // The middle of the image is inside every side, even if the axis isn't.
+        let middle = edges.iter().fold(Vector3::zeros(), |middle, edge| middle + edge);
+        let mut normals = [Vector3::zeros(); 4];
+        for (i, normal) in normals.iter_mut().enumerate() {
+            let mut side = edges[i].cross(&edges[(i + 1) % 4]).normalize();
+            if side.dot(&middle) < 0. {
+                side = -side;
+            }
+            *normal = side;
+        }
+        Frustum {
+            apex: apex,
+            normals: normals,
+            axis: axis,
+        }
+    }
+
+    /// Returns true if the point might be visible in the image.
+    pub fn contains(&self, socs: &Point<Socs>) -> bool {
+        self.contains_direction(**socs - self.apex)
+    }
+
+    /// Tests every point of a batch in the scanner's own coordinate system, replacing `contains`
+    /// with whether each might be visible in the image.
+    pub fn contains_batch(&self, batch: &Batch, contains: &mut Vec<bool>) {
+        contains.clear();
+        contains.extend((0..batch.len()).map(|i| {
+            let (x, y, z) = batch.get(i);
+            self.contains_direction(Point3::new(x, y, z) - self.apex)
+        }));
+    }
+
+    fn contains_direction(&self, direction: Vector3<f64>) -> bool {
+        direction.dot(&self.axis) > 0. &&
+            self.normals
+                .iter()
+                .all(|normal| direction.dot(normal) >= -TOLERANCE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera_calibration() -> CameraCalibration {
+        CameraCalibration {
+            name: "camera".to_string(),
+            cx: 320.,
+            cy: 256.,
+            fx: 500.,
+            fy: 500.,
+            k1: 0.,
+            k2: 0.,
+            k3: 0.,
+            k4: 0.,
+            p1: 0.,
+            p2: 0.,
+            tan_max_horz: 0.6,
+            tan_max_vert: 0.5,
+            tan_min_horz: -0.6,
+            tan_min_vert: -0.5,
+            width: 640,
+            height: 512,
+        }
+    }
+
+    fn contains(frustum: &Frustum, points: &[(f64, f64, f64)]) -> Vec<bool> {
+        let mut contains = Vec::new();
+        frustum.contains_batch(&points.iter().cloned().collect(), &mut contains);
+        assert_eq!(
+            contains,
+            points
+                .iter()
+                .map(|&(x, y, z)| frustum.contains(&Point::socs(x, y, z)))
+                .collect::<Vec<_>>()
+        );
+        contains
+    }
+
+    #[test]
+    fn inside_and_outside() {
+        let frustum = Frustum::from_fn(|x, y, z| Point3::new(x, y, z), &camera_calibration());
+        let points = [
+            (0., 0., 10.),
+            (5.9, 4.9, 10.),
+            (-5.9, -4.9, 10.),
+            (6.1, 0., 10.),
+            (0., -5.1, 10.),
+            (0., 0., -10.),
+            (0., 0., 0.),
+        ];
+        assert_eq!(
+            vec![true, true, true, false, false, false, false],
+            contains(&frustum, &points)
+        );
+    }
+
+    #[test]
+    fn moved_camera() {
+        // The camera is at (1, 2, 3) in SOCS, looking along SOCS x, with its x along SOCS y.
+        let frustum = Frustum::from_fn(
+            |x, y, z| Point3::new(1. + z, 2. + x, 3. + y),
+            &camera_calibration(),
+        );
+        let points = [
+            (11., 2., 3.),
+            (11., 7.9, 7.9),
+            (11., 8.1, 3.),
+            (11., 2., 8.1),
+            (-9., 2., 3.),
+        ];
+        assert_eq!(vec![true, true, false, false, false], contains(&frustum, &points));
+    }
+}
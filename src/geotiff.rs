@@ -0,0 +1,452 @@
+use byteorder::{BigEndian, ByteOrder, LittleEndian, WriteBytesExt};
+use flate2::read::ZlibDecoder;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+const IMAGE_WIDTH: u16 = 256;
+const IMAGE_LENGTH: u16 = 257;
+const BITS_PER_SAMPLE: u16 = 258;
+const COMPRESSION: u16 = 259;
+const PHOTOMETRIC_INTERPRETATION: u16 = 262;
+const STRIP_OFFSETS: u16 = 273;
+const SAMPLES_PER_PIXEL: u16 = 277;
+const ROWS_PER_STRIP: u16 = 278;
+const STRIP_BYTE_COUNTS: u16 = 279;
+const PLANAR_CONFIGURATION: u16 = 284;
+const PREDICTOR: u16 = 317;
+const TILE_WIDTH: u16 = 322;
+const TILE_LENGTH: u16 = 323;
+const TILE_OFFSETS: u16 = 324;
+const TILE_BYTE_COUNTS: u16 = 325;
+const SAMPLE_FORMAT: u16 = 339;
+const MODEL_PIXEL_SCALE: u16 = 33550;
+const MODEL_TIEPOINT: u16 = 33922;
+const GEO_KEY_DIRECTORY: u16 = 34735;
+const GDAL_NODATA: u16 = 42113;
+
+/// A single-band, north-up georeferenced raster, e.g. a thermal orthomosaic.
+#[derive(Clone, Debug)]
+pub struct GeoTiff {
+    pub width: usize,
+    pub height: usize,
+    /// The model coordinates of the upper left corner of the upper left pixel.
+    pub origin: (f64, f64),
+    /// The size of a pixel in model units.
+    pub scale: (f64, f64),
+    pub nodata: Option<f64>,
+    data: Vec<f64>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Endian {
+    Little,
+    Big,
+}
+
+struct Entry {
+    field_type: u16,
+    count: usize,
+    offset: usize,
+}
+
+impl GeoTiff {
+    /// Creates a new raster from row-major data, starting at the upper left pixel.
+    pub fn new(
+        width: usize,
+        height: usize,
+        origin: (f64, f64),
+        scale: (f64, f64),
+        nodata: Option<f64>,
+        data: Vec<f64>,
+    ) -> GeoTiff {
+        assert_eq!(width * height, data.len());
+        GeoTiff {
+            width: width,
+            height: height,
+            origin: origin,
+            scale: scale,
+            nodata: nodata,
+            data: data,
+        }
+    }
+
+    /// Reads the first band of a GeoTIFF.
+    ///
+    /// Only uncompressed and deflate-compressed strips or tiles without a predictor are supported,
+    /// e.g. `gdal_translate -co COMPRESS=DEFLATE` output.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<GeoTiff> {
+        let mut bytes = Vec::new();
+        File::open(path)?.read_to_end(&mut bytes)?;
+        let endian = match bytes.get(0..2) {
+            Some(b"II") => Endian::Little,
+            Some(b"MM") => Endian::Big,
+            _ => return Err(invalid_data("not a tiff file")),
+        };
+        if endian.u16(&bytes, 2)? != 42 {
+            return Err(invalid_data("not a tiff file (BigTIFF is not supported)"));
+        }
+        let ifd = endian.u32(&bytes, 4)? as usize;
+        let mut entries = Vec::new();
+        for i in 0..endian.u16(&bytes, ifd)? as usize {
+            let start = ifd + 2 + i * 12;
+            let tag = endian.u16(&bytes, start)?;
+            let field_type = endian.u16(&bytes, start + 2)?;
+            let count = endian.u32(&bytes, start + 4)? as usize;
+            let size = count * type_size(field_type)?;
+            let offset = if size <= 4 {
+                start + 8
+            } else {
+                endian.u32(&bytes, start + 8)? as usize
+            };
+            entries.push((
+                tag,
+                Entry {
+                    field_type: field_type,
+                    count: count,
+                    offset: offset,
+                },
+            ));
+        }
+        let get = |tag: u16| -> io::Result<Option<Vec<f64>>> {
+            match entries.iter().find(|&&(t, _)| t == tag) {
+                Some(&(_, ref entry)) => endian.values(&bytes, entry).map(Some),
+                None => Ok(None),
+            }
+        };
+        let require = |tag: u16| -> io::Result<Vec<f64>> {
+            get(tag)?.ok_or_else(|| invalid_data(format!("missing tiff tag {}", tag)))
+        };
+
+        let width = require(IMAGE_WIDTH)?[0] as usize;
+        let height = require(IMAGE_LENGTH)?[0] as usize;
+        let bits_per_sample = require(BITS_PER_SAMPLE)?[0] as usize;
+        let samples_per_pixel = get(SAMPLES_PER_PIXEL)?.map(|v| v[0] as usize).unwrap_or(1);
+        let sample_format = get(SAMPLE_FORMAT)?.map(|v| v[0] as u16).unwrap_or(1);
+        let compression = get(COMPRESSION)?.map(|v| v[0] as u16).unwrap_or(1);
+        if compression != 1 && compression != 8 && compression != 32946 {
+            return Err(invalid_data(format!(
+                "unsupported tiff compression {}, convert with gdal_translate -co COMPRESS=DEFLATE",
+                compression
+            )));
+        }
+        if get(PREDICTOR)?.map(|v| v[0] != 1.).unwrap_or(false) {
+            return Err(invalid_data("tiff predictors are not supported"));
+        }
+        if get(PLANAR_CONFIGURATION)?.map(|v| v[0] != 1.).unwrap_or(false) {
+            return Err(invalid_data(
+                "separate tiff planes are not supported, convert with -co INTERLEAVE=PIXEL",
+            ));
+        }
+        let (chunk_width, chunk_height, offsets, byte_counts) = match get(TILE_OFFSETS)? {
+            Some(offsets) => (
+                require(TILE_WIDTH)?[0] as usize,
+                require(TILE_LENGTH)?[0] as usize,
+                offsets,
+                require(TILE_BYTE_COUNTS)?,
+            ),
+            None => (
+                width,
+                get(ROWS_PER_STRIP)?.map(|v| v[0] as usize).unwrap_or(height).min(height),
+                require(STRIP_OFFSETS)?,
+                require(STRIP_BYTE_COUNTS)?,
+            ),
+        };
+        let scale = require(MODEL_PIXEL_SCALE)?;
+        let tiepoint = require(MODEL_TIEPOINT)?;
+        if scale.len() < 2 || tiepoint.len() < 6 {
+            return Err(invalid_data("invalid GeoTIFF georeferencing"));
+        }
+        let nodata = match entries.iter().find(|&&(t, _)| t == GDAL_NODATA) {
+            Some(&(_, ref entry)) => {
+                let end = (entry.offset + entry.count).min(bytes.len());
+                String::from_utf8_lossy(&bytes[entry.offset..end])
+                    .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+                    .parse::<f64>()
+                    .ok()
+            }
+            None => None,
+        };
+
+        let sample_size = bits_per_sample / 8;
+        let pixel_size = sample_size * samples_per_pixel;
+        let chunks_across = (width + chunk_width - 1) / chunk_width;
+        let mut data = vec![nodata.unwrap_or(0.); width * height];
+        for (i, (&offset, &byte_count)) in offsets.iter().zip(byte_counts.iter()).enumerate() {
+            let (offset, byte_count) = (offset as usize, byte_count as usize);
+            let raw = bytes.get(offset..offset + byte_count).ok_or_else(|| {
+                invalid_data("tiff strip or tile is outside of the file")
+            })?;
+            let chunk = if compression == 1 {
+                raw.to_vec()
+            } else {
+                let mut chunk = Vec::new();
+                ZlibDecoder::new(raw).read_to_end(&mut chunk)?;
+                chunk
+            };
+            let (x0, y0) = ((i % chunks_across) * chunk_width, (i / chunks_across) * chunk_height);
+            for row in 0..chunk_height {
+                for column in 0..chunk_width {
+                    let (x, y) = (x0 + column, y0 + row);
+                    let start = (row * chunk_width + column) * pixel_size;
+                    if x >= width || y >= height || start + sample_size > chunk.len() {
+                        continue;
+                    }
+                    data[y * width + x] = endian.sample(
+                        &chunk[start..start + sample_size],
+                        sample_format,
+                    )?;
+                }
+            }
+        }
+        Ok(GeoTiff {
+            width: width,
+            height: height,
+            origin: (
+                tiepoint[3] - tiepoint[0] * scale[0],
+                tiepoint[4] + tiepoint[1] * scale[1],
+            ),
+            scale: (scale[0], scale[1]),
+            nodata: nodata,
+            data: data,
+        })
+    }
+
+    /// Returns the value of the pixel that contains these model coordinates.
+    ///
+    /// Returns `None` for nodata pixels and coordinates outside of the raster.
+    pub fn get(&self, x: f64, y: f64) -> Option<f64> {
+        let column = ((x - self.origin.0) / self.scale.0).floor();
+        let row = ((self.origin.1 - y) / self.scale.1).floor();
+        if column < 0. || row < 0. || column >= self.width as f64 || row >= self.height as f64 {
+            return None;
+        }
+        let value = self.data[row as usize * self.width + column as usize];
+        if value.is_nan() || self.nodata == Some(value) {
+            None
+        } else {
+            Some(value)
+        }
+    }
+
+    /// Writes this raster as an uncompressed, little-endian, 32-bit float GeoTIFF.
+    ///
+    /// No coordinate reference system is recorded, since the project's global coordinate system
+    /// isn't known to us.
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        const SHORT: u16 = 3;
+        const LONG: u16 = 4;
+        const ASCII: u16 = 2;
+        const DOUBLE: u16 = 12;
+
+        let mut image = Vec::with_capacity(self.data.len() * 4);
+        for &value in &self.data {
+            image.write_f32::<LittleEndian>(value as f32)?;
+        }
+        let shorts = |values: &[u16]| {
+            let mut bytes = Vec::new();
+            for &value in values {
+                bytes.write_u16::<LittleEndian>(value).unwrap();
+            }
+            bytes
+        };
+        let longs = |value: u32| {
+            let mut bytes = Vec::new();
+            bytes.write_u32::<LittleEndian>(value).unwrap();
+            bytes
+        };
+        let doubles = |values: &[f64]| {
+            let mut bytes = Vec::new();
+            for &value in values {
+                bytes.write_f64::<LittleEndian>(value).unwrap();
+            }
+            bytes
+        };
+        let mut entries: Vec<(u16, u16, usize, Vec<u8>)> = vec![
+            (IMAGE_WIDTH, LONG, 1, longs(self.width as u32)),
+            (IMAGE_LENGTH, LONG, 1, longs(self.height as u32)),
+            (BITS_PER_SAMPLE, SHORT, 1, shorts(&[32])),
+            (COMPRESSION, SHORT, 1, shorts(&[1])),
+            (PHOTOMETRIC_INTERPRETATION, SHORT, 1, shorts(&[1])),
+            // The strip offset is filled in once we know where the image data goes.
+            (STRIP_OFFSETS, LONG, 1, longs(0)),
+            (SAMPLES_PER_PIXEL, SHORT, 1, shorts(&[1])),
+            (ROWS_PER_STRIP, LONG, 1, longs(self.height as u32)),
+            (STRIP_BYTE_COUNTS, LONG, 1, longs(image.len() as u32)),
+            (SAMPLE_FORMAT, SHORT, 1, shorts(&[3])),
+            (MODEL_PIXEL_SCALE, DOUBLE, 3, doubles(&[self.scale.0, self.scale.1, 0.])),
+            (
+                MODEL_TIEPOINT,
+                DOUBLE,
+                6,
+                doubles(&[0., 0., 0., self.origin.0, self.origin.1, 0.]),
+            ),
+            // Version 1.1.0 with two keys: a projected model, where pixels are areas.
+            (
+                GEO_KEY_DIRECTORY,
+                SHORT,
+                12,
+                shorts(&[1, 1, 0, 2, 1024, 0, 1, 1, 1025, 0, 1, 1]),
+            ),
+        ];
+        if let Some(nodata) = self.nodata {
+            let mut bytes = nodata.to_string().into_bytes();
+            bytes.push(0);
+            entries.push((GDAL_NODATA, ASCII, bytes.len(), bytes));
+        }
+
+        let ifd_size = 2 + entries.len() * 12 + 4;
+        let mut extra_offset = 8 + ifd_size;
+        let extra_size = entries
+            .iter()
+            .filter(|entry| entry.3.len() > 4)
+            .map(|entry| entry.3.len())
+            .sum::<usize>();
+        let image_offset = extra_offset + extra_size;
+        let mut ifd = Vec::with_capacity(ifd_size);
+        let mut extra = Vec::with_capacity(extra_size);
+        ifd.write_u16::<LittleEndian>(entries.len() as u16)?;
+        for &mut (tag, field_type, count, ref mut value) in &mut entries {
+            if tag == STRIP_OFFSETS {
+                *value = longs(image_offset as u32);
+            }
+            ifd.write_u16::<LittleEndian>(tag)?;
+            ifd.write_u16::<LittleEndian>(field_type)?;
+            ifd.write_u32::<LittleEndian>(count as u32)?;
+            if value.len() > 4 {
+                ifd.write_u32::<LittleEndian>(extra_offset as u32)?;
+                extra.extend_from_slice(value);
+                extra_offset += value.len();
+            } else {
+                let mut padded = value.clone();
+                padded.resize(4, 0);
+                ifd.extend_from_slice(&padded);
+            }
+        }
+        ifd.write_u32::<LittleEndian>(0)?;
+
+        let mut file = io::BufWriter::new(File::create(path)?);
+        file.write_all(b"II")?;
+        file.write_u16::<LittleEndian>(42)?;
+        file.write_u32::<LittleEndian>(8)?;
+        file.write_all(&ifd)?;
+        file.write_all(&extra)?;
+        file.write_all(&image)?;
+        Ok(())
+    }
+}
+
+impl Endian {
+    fn u16(&self, bytes: &[u8], offset: usize) -> io::Result<u16> {
+        let bytes = slice(bytes, offset, 2)?;
+        Ok(match *self {
+            Endian::Little => LittleEndian::read_u16(bytes),
+            Endian::Big => BigEndian::read_u16(bytes),
+        })
+    }
+
+    fn u32(&self, bytes: &[u8], offset: usize) -> io::Result<u32> {
+        let bytes = slice(bytes, offset, 4)?;
+        Ok(match *self {
+            Endian::Little => LittleEndian::read_u32(bytes),
+            Endian::Big => BigEndian::read_u32(bytes),
+        })
+    }
+
+    fn values(&self, bytes: &[u8], entry: &Entry) -> io::Result<Vec<f64>> {
+        let size = type_size(entry.field_type)?;
+        let format = match entry.field_type {
+            1 | 2 | 3 | 4 => 1,
+            11 | 12 => 3,
+            _ => return Err(invalid_data("unsupported tiff field type")),
+        };
+        (0..entry.count)
+            .map(|i| {
+                let bytes = slice(bytes, entry.offset + i * size, size)?;
+                self.sample(bytes, format)
+            })
+            .collect()
+    }
+
+    /// Converts one sample to a float, given the tiff sample format.
+    fn sample(&self, bytes: &[u8], format: u16) -> io::Result<f64> {
+        Ok(match (format, bytes.len(), *self) {
+            (1, 1, _) => f64::from(bytes[0]),
+            (2, 1, _) => f64::from(bytes[0] as i8),
+            (1, 2, Endian::Little) => f64::from(LittleEndian::read_u16(bytes)),
+            (1, 2, Endian::Big) => f64::from(BigEndian::read_u16(bytes)),
+            (2, 2, Endian::Little) => f64::from(LittleEndian::read_i16(bytes)),
+            (2, 2, Endian::Big) => f64::from(BigEndian::read_i16(bytes)),
+            (1, 4, Endian::Little) => f64::from(LittleEndian::read_u32(bytes)),
+            (1, 4, Endian::Big) => f64::from(BigEndian::read_u32(bytes)),
+            (2, 4, Endian::Little) => f64::from(LittleEndian::read_i32(bytes)),
+            (2, 4, Endian::Big) => f64::from(BigEndian::read_i32(bytes)),
+            (3, 4, Endian::Little) => f64::from(LittleEndian::read_f32(bytes)),
+            (3, 4, Endian::Big) => f64::from(BigEndian::read_f32(bytes)),
+            (3, 8, Endian::Little) => LittleEndian::read_f64(bytes),
+            (3, 8, Endian::Big) => BigEndian::read_f64(bytes),
+            _ => {
+                return Err(invalid_data(format!(
+                    "unsupported tiff sample format {} with {} bytes",
+                    format,
+                    bytes.len()
+                )))
+            }
+        })
+    }
+}
+
+fn type_size(field_type: u16) -> io::Result<usize> {
+    match field_type {
+        1 | 2 | 6 | 7 => Ok(1),
+        3 | 8 => Ok(2),
+        4 | 9 | 11 => Ok(4),
+        5 | 10 | 12 => Ok(8),
+        _ => Err(invalid_data(format!("unknown tiff field type {}", field_type))),
+    }
+}
+
+fn slice(bytes: &[u8], offset: usize, len: usize) -> io::Result<&[u8]> {
+    bytes.get(offset..offset + len).ok_or_else(
+        || invalid_data("unexpected end of tiff file"),
+    )
+}
+
+fn invalid_data<E>(err: E) -> io::Error
+where
+    E: Into<Box<Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    #[test]
+    fn round_trip() {
+        let data = vec![1., 2., 3., -9999., 5., 6.];
+        let geotiff = GeoTiff::new(3, 2, (100., 200.), (2., 4.), Some(-9999.), data);
+        let path = env::temp_dir().join(format!("tce-geotiff-{}.tif", process::id()));
+        geotiff.write_to_path(&path).unwrap();
+        let read = GeoTiff::from_path(&path);
+        fs::remove_file(&path).unwrap();
+        let read = read.unwrap();
+        assert_eq!((3, 2), (read.width, read.height));
+        assert_eq!((100., 200.), read.origin);
+        assert_eq!((2., 4.), read.scale);
+        assert_eq!(Some(-9999.), read.nodata);
+        assert_eq!(Some(1.), read.get(100.5, 199.5));
+        assert_eq!(Some(3.), read.get(105.9, 196.1));
+        assert_eq!(Some(6.), read.get(105., 193.));
+        assert_eq!(None, read.get(101., 193.));
+        assert_eq!(None, read.get(99.9, 199.));
+        assert_eq!(None, read.get(106., 199.));
+        assert_eq!(None, read.get(101., 192.));
+        assert_eq!(None, read.get(101., 200.1));
+    }
+}
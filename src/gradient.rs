@@ -0,0 +1,122 @@
+use clap::ArgMatches;
+use palette::{Gradient, Rgb};
+use png;
+use raster;
+
+/// The height of the tick marks below the strip, in pixels.
+const TICK_HEIGHT: usize = 4;
+
+/// How much the label font is scaled up from its 3x5 pixel glyphs.
+const FONT_SCALE: usize = 2;
+
+/// Returns the gradient that maps temperatures to colors, from blue at `min` to red at `max`.
+pub fn temperature_gradient(min: f32, max: f32) -> Gradient<Rgb> {
+    Gradient::with_domain(vec![(min, Rgb::new(0., 0., 1.)), (max, Rgb::new(1., 0., 0.))])
+}
+
+/// Renders the temperature gradient to a png strip, with labeled ticks underneath.
+pub fn preview(matches: &ArgMatches) {
+    let min = value_t!(matches, "min-temperature", f32).unwrap();
+    let max = value_t!(matches, "max-temperature", f32).unwrap();
+    let width = value_t!(matches, "width", usize).unwrap();
+    let height = value_t!(matches, "height", usize).unwrap();
+    let ticks = value_t!(matches, "ticks", usize).unwrap();
+    if ticks < 2 {
+        panic!("--ticks must be at least 2");
+    }
+    if width < 2 {
+        panic!("--width must be at least 2");
+    }
+    let gradient = temperature_gradient(min, max);
+    let label_height = 5 * FONT_SCALE;
+    let total_height = height + TICK_HEIGHT + 2 + label_height;
+    let mut data = vec![0; width * total_height * 3];
+    for column in 0..width {
+        let temperature = min + (max - min) * column as f32 / (width - 1) as f32;
+        let color = gradient.get(temperature);
+        let rgb = [
+            (255. * color.red) as u8,
+            (255. * color.green) as u8,
+            (255. * color.blue) as u8,
+        ];
+        for row in 0..height {
+            let index = (row * width + column) * 3;
+            data[index..index + 3].copy_from_slice(&rgb);
+        }
+    }
+    for tick in 0..ticks {
+        let column = (width - 1) * tick / (ticks - 1);
+        for row in height..height + TICK_HEIGHT {
+            set_white(&mut data, width, column, row);
+        }
+        let temperature = min + (max - min) * tick as f32 / (ticks - 1) as f32;
+        let label = label(temperature);
+        let label_width = label.len() * 4 * FONT_SCALE - FONT_SCALE;
+        let left = column
+            .saturating_sub(label_width / 2)
+            .min(width.saturating_sub(label_width));
+        draw(&mut data, width, left, height + TICK_HEIGHT + 2, &label);
+    }
+    let outfile = matches.value_of("OUTFILE").unwrap();
+    info!("Writing {}...", outfile);
+    raster::write_png(outfile, width, total_height, png::ColorType::RGB, &data)
+        .expect(&format!("Could not write {}", outfile));
+}
+
+/// Formats a temperature with at most one decimal place, e.g. "-40" or "12.5".
+fn label(temperature: f32) -> String {
+    let label = format!("{:.1}", temperature);
+    let label = label.trim_right_matches('0').trim_right_matches('.');
+    if label == "-0" {
+        "0".to_string()
+    } else {
+        label.to_string()
+    }
+}
+
+/// Draws text in white, with its top left corner at `left` and `top`, clipping at the edges.
+fn draw(data: &mut [u8], width: usize, left: usize, top: usize, text: &str) {
+    let height = data.len() / 3 / width;
+    for (i, c) in text.chars().enumerate() {
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+                for dy in 0..FONT_SCALE {
+                    for dx in 0..FONT_SCALE {
+                        let x = left + (i * 4 + column) * FONT_SCALE + dx;
+                        let y = top + row * FONT_SCALE + dy;
+                        if x < width && y < height {
+                            set_white(data, width, x, y);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn set_white(data: &mut [u8], width: usize, column: usize, row: usize) {
+    let index = (row * width + column) * 3;
+    data[index..index + 3].copy_from_slice(&[255, 255, 255]);
+}
+
+/// Returns the rows of a 3x5 glyph, most significant bit on the left.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        _ => [0; 5],
+    }
+}
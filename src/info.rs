@@ -0,0 +1,252 @@
+//! Describes input files without colorizing them.
+
+use clap::ArgMatches;
+use irb::Irb;
+use nalgebra::Projective3;
+use project;
+use scanifc::point3d::Stream;
+use serde_json::{self, Value};
+use std::fs;
+use std::path::Path;
+
+/// The largest image dimension we look for, in pixels.
+const MAX_DIMENSION: i32 = 1 << 14;
+
+/// Prints the dimensions, capture time, and temperature statistics of each irb file.
+///
+/// The capture time is parsed from the file stem with `--image-time-format`, like when colorizing.
+pub fn irb(matches: &ArgMatches) {
+    let image_time_format = matches.value_of("image-time-format");
+    for path in matches.values_of("FILE").unwrap().map(Path::new) {
+        let temporary_file = if path.extension().map(|e| e.to_string_lossy().to_lowercase()) ==
+            Some("gz".to_string())
+        {
+            Some(::TemporaryFile::gunzip(path).expect(&format!(
+                "Could not decompress {}",
+                path.display()
+            )))
+        } else {
+            None
+        };
+        let irb_path = temporary_file
+            .as_ref()
+            .map(|temporary_file| temporary_file.0.as_path())
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned();
+        let irb = Irb::from_path(&irb_path).expect(&format!(
+            "Could not open {}",
+            path.display()
+        ));
+        let (width, height) = dimensions(&irb);
+        info!("{}:", path.display());
+        info!("  - dimensions: {} x {}", width, height);
+        if let Ok(metadata) = fs::metadata(path) {
+            info!("  - file size: {} bytes", metadata.len());
+        }
+        if let Some(format) = image_time_format {
+            let file_stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            match ::parse_time(&file_stem, format) {
+                Some(time) => info!("  - captured: {}", time),
+                None => info!("  - captured: unknown, the file stem doesn't match {}", format),
+            }
+        }
+        let mut statistics = Statistics::default();
+        for y in 0..height {
+            for x in 0..width {
+                match irb.temperature(x, y) {
+                    Ok(temperature) => statistics.add(temperature - 273.15),
+                    Err(_) => statistics.unreadable += 1,
+                }
+            }
+        }
+        if statistics.count > 0 {
+            info!(
+                "  - temperatures: {:.2} to {:.2} °C, mean {:.2} °C, standard deviation {:.2} °C",
+                statistics.min,
+                statistics.max,
+                statistics.mean(),
+                statistics.standard_deviation()
+            );
+        }
+        if statistics.unreadable > 0 {
+            info!("  - unreadable pixels: {}", statistics.unreadable);
+        }
+    }
+}
+
+/// Prints the point count, the time span, and the pps-synced points of each rxp file.
+///
+/// Counting the points and finding the time span reads the whole file, so with `--estimate` the
+/// count is estimated from the file size instead, like for the progress bars, and only the first
+/// points are checked for pps sync, like when colorizing.
+pub fn rxp(matches: &ArgMatches) {
+    let estimate = matches.is_present("estimate");
+    for path in matches.values_of("FILE").unwrap().map(Path::new) {
+        let bytes = fs::metadata(path)
+            .expect(&format!("Could not read {}", path.display()))
+            .len();
+        info!("{}:", path.display());
+        info!("  - file size: {} bytes", bytes);
+        let stream = Stream::from_path(path)
+            .sync_to_pps(false)
+            .open()
+            .expect(&format!("Could not open {}", path.display()));
+        if estimate {
+            let has_synced_points = stream
+                .take(::PPS_PROBE_POINTS)
+                .any(|point| point.map(|point| point.is_pps_locked).unwrap_or(false));
+            info!(
+                "  - pps-synced points: {} in the first {}",
+                if has_synced_points { "some" } else { "none" },
+                ::PPS_PROBE_POINTS
+            );
+            info!("  - points: about {}", bytes / ::RXP_BYTES_PER_POINT);
+            continue;
+        }
+        let (mut count, mut synced) = (0, 0);
+        let mut span: Option<(f64, f64)> = None;
+        for point in stream {
+            let point = match point {
+                Ok(point) => point,
+                Err(err) => {
+                    warn!("could not read all of {}: {:?}", path.display(), err);
+                    break;
+                }
+            };
+            count += 1;
+            if point.is_pps_locked {
+                synced += 1;
+            }
+            span = Some(match span {
+                Some((start, end)) => (start.min(point.time), end.max(point.time)),
+                None => (point.time, point.time),
+            });
+        }
+        info!("  - points: {}", count);
+        info!("  - pps-synced points: {}", synced);
+        if let Some((start, end)) = span {
+            info!("  - time span: {:.3} to {:.3} s ({:.1} s)", start, end, end - start);
+        }
+    }
+}
+
+/// Prints the scan positions, scans, images, calibrations, POP, and SOPs of a project as JSON, for
+/// scheduling jobs.
+///
+/// Transforms are row-major 4x4 matrices, and everything is sorted by name.
+pub fn project(matches: &ArgMatches) {
+    let project = project::open(matches.value_of("PROJECT").unwrap());
+    let mut camera_calibrations = project.camera_calibrations.values().collect::<Vec<_>>();
+    camera_calibrations.sort_by_key(|camera_calibration| &camera_calibration.name);
+    let mut mount_calibrations = project.mount_calibrations.values().collect::<Vec<_>>();
+    mount_calibrations.sort_by_key(|mount_calibration| &mount_calibration.name);
+    let mut scan_positions = project.scan_positions.values().collect::<Vec<_>>();
+    scan_positions.sort_by_key(|scan_position| &scan_position.name);
+    let description = json!({
+        "path": project.path.display().to_string(),
+        "pop": matrix(&project.pop),
+        "camera_calibrations": camera_calibrations
+            .iter()
+            .map(|camera_calibration| {
+                json!({
+                    "name": camera_calibration.name,
+                    "width": camera_calibration.width,
+                    "height": camera_calibration.height,
+                    "tan_min_horz": camera_calibration.tan_min_horz,
+                    "tan_max_horz": camera_calibration.tan_max_horz,
+                    "tan_min_vert": camera_calibration.tan_min_vert,
+                    "tan_max_vert": camera_calibration.tan_max_vert,
+                })
+            })
+            .collect::<Vec<_>>(),
+        "mount_calibrations": mount_calibrations
+            .iter()
+            .map(|mount_calibration| json!({"name": mount_calibration.name}))
+            .collect::<Vec<_>>(),
+        "scan_positions": scan_positions
+            .iter()
+            .map(|scan_position| {
+                let mut scans = scan_position.singlescan_rxp_paths(&project);
+                scans.sort();
+                let mut images = scan_position.images.values().collect::<Vec<_>>();
+                images.sort_by_key(|image| &image.name);
+                json!({
+                    "name": scan_position.name,
+                    "sop": matrix(&scan_position.sop),
+                    "scans": scans
+                        .iter()
+                        .map(|scan| scan.display().to_string())
+                        .collect::<Vec<_>>(),
+                    "images": images
+                        .iter()
+                        .map(|image| {
+                            json!({
+                                "name": image.name,
+                                "camera_calibration": image.camera_calibration_name,
+                                "mount_calibration": image.mount_calibration_name,
+                                "cop": matrix(&image.cop),
+                            })
+                        })
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect::<Vec<_>>(),
+    });
+    println!("{}", serde_json::to_string_pretty(&description).unwrap());
+}
+
+/// Returns the rows of a transform's matrix.
+fn matrix(transform: &Projective3<f64>) -> Value {
+    json!(
+        (0..4)
+            .map(|i| (0..4).map(|j| transform[(i, j)]).collect::<Vec<_>>())
+            .collect::<Vec<_>>()
+    )
+}
+
+/// Returns the width and height of an image, which the irb handle only tells us by failing to read
+/// the pixels outside of it.
+fn dimensions(irb: &Irb) -> (i32, i32) {
+    let extent = |is_inside: &Fn(i32) -> bool| {
+        (0..MAX_DIMENSION).take_while(|&i| is_inside(i)).count() as i32
+    };
+    (
+        extent(&|x| irb.temperature(x, 0).is_ok()),
+        extent(&|y| irb.temperature(0, y).is_ok()),
+    )
+}
+
+/// Running statistics of the temperatures of an image, in °C.
+#[derive(Debug, Default)]
+struct Statistics {
+    count: u64,
+    unreadable: u64,
+    min: f64,
+    max: f64,
+    sum: f64,
+    sum_of_squares: f64,
+}
+
+impl Statistics {
+    fn add(&mut self, temperature: f64) {
+        if self.count == 0 {
+            self.min = temperature;
+            self.max = temperature;
+        } else {
+            self.min = self.min.min(temperature);
+            self.max = self.max.max(temperature);
+        }
+        self.count += 1;
+        self.sum += temperature;
+        self.sum_of_squares += temperature * temperature;
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum / self.count as f64
+    }
+
+    fn standard_deviation(&self) -> f64 {
+        (self.sum_of_squares / self.count as f64 - self.mean().powi(2)).max(0.).sqrt()
+    }
+}
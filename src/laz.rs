@@ -0,0 +1,52 @@
+//! Reads and writes LAZ files, which las-rs can't, by converting them with laszip.
+//!
+//! This runs the `laszip` executable (from LAStools or LASzip), which must be on the `PATH`.
+
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+use std::process::Command;
+
+/// Returns an error if laszip can't be run, e.g. because it isn't on the `PATH`.
+pub fn check() -> io::Result<()> {
+    Command::new("laszip")
+        .arg("-version")
+        .output()
+        .map(|_| ())
+        .map_err(|err| Error::new(err.kind(), format!("could not run laszip: {}", err)))
+}
+
+/// Decompresses a LAZ file to a las file.
+pub fn decompress(laz: &Path, las: &Path) -> io::Result<()> {
+    laszip(laz, las, &[])
+}
+
+/// Compresses a las file to a LAZ file, with an optional number of points per chunk.
+pub fn compress(las: &Path, laz: &Path, chunk_size: Option<u32>) -> io::Result<()> {
+    let args = chunk_size
+        .map(|chunk_size| vec!["-chunk_size".to_string(), chunk_size.to_string()])
+        .unwrap_or_default();
+    laszip(las, laz, &args)
+}
+
+fn laszip(input: &Path, output: &Path, args: &[String]) -> io::Result<()> {
+    let output = Command::new("laszip")
+        .arg("-i")
+        .arg(input)
+        .arg("-o")
+        .arg(output)
+        .args(args)
+        .output()
+        .map_err(|err| Error::new(err.kind(), format!("could not run laszip: {}", err)))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(Error::new(
+            ErrorKind::Other,
+            format!(
+                "laszip exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ))
+    }
+}
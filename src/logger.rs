@@ -0,0 +1,226 @@
+use ansi_term::{Colour, Style};
+use chrono::Local;
+use log::{self, Level, LevelFilter, Log, Metadata, Record};
+use serde_json::Value;
+use std::fs::File;
+use std::io::Write;
+use std::panic;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use tui;
+
+/// The most verbose level printed to the terminal, as a `LevelFilter` discriminant.
+static CONSOLE_LEVEL: AtomicUsize = AtomicUsize::new(0);
+
+/// Are terminal messages colored?
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// The colors of the keys of nested `- key: value` lines, by depth.
+const KEY_COLOURS: [Colour; 3] = [Colour::Cyan, Colour::Blue, Colour::Purple];
+
+/// Are progress events written to stderr as JSON lines?
+static JSON_EVENTS: AtomicBool = AtomicBool::new(false);
+
+/// Are progress events drawn as a full-screen status display?
+static TUI_EVENTS: AtomicBool = AtomicBool::new(false);
+
+/// How progress is reported.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProgressFormat {
+    /// Progress bars and plain messages.
+    Text,
+    /// JSON lines on stderr, one per event, with warnings and errors as `warning` and `error`
+    /// events.
+    Json,
+    /// A status display of each scan position and the overall ETA, redrawn on stderr, with the
+    /// latest warnings and errors. Other messages aren't printed while it's drawn.
+    Tui,
+}
+
+/// Prints log messages to the terminal, with warnings and errors going to stderr, and optionally
+/// to a log file.
+struct Logger {
+    level: LevelFilter,
+    file: Option<Arc<Mutex<File>>>,
+}
+
+/// Installs the logger, which will print messages up to and including `level`.
+///
+/// If there's a log file, it gets timestamped messages up to at least `Level::Info`, regardless of
+/// `level`, along with any panics. With `color`, terminal messages are colored by level and by
+/// structure, but the log file is always plain.
+pub fn init(
+    level: LevelFilter,
+    log_file: Option<&Path>,
+    progress_format: ProgressFormat,
+    color: bool,
+) {
+    COLOR.store(color, Ordering::SeqCst);
+    let json_events = progress_format == ProgressFormat::Json;
+    JSON_EVENTS.store(json_events, Ordering::SeqCst);
+    TUI_EVENTS.store(progress_format == ProgressFormat::Tui, Ordering::SeqCst);
+    let file = log_file.map(|path| {
+        Arc::new(Mutex::new(File::create(path).expect(&format!(
+            "Could not create log file {}",
+            path.display()
+        ))))
+    });
+    let max_level = if file.is_some() {
+        level.max(LevelFilter::Info)
+    } else {
+        level
+    };
+    if file.is_some() || emits_events() {
+        let file = file.clone();
+        let default_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if let Some(file) = file.as_ref() {
+                write_line(file, Level::Error, &info.to_string());
+            }
+            if emits_events() {
+                // Keep stderr parseable, or the error on the status display.
+                event(json!({"event": "error", "message": info.to_string()}));
+            } else {
+                default_hook(info);
+            }
+        }));
+    }
+    log::set_boxed_logger(Box::new(Logger {
+        level: level,
+        file: file,
+    })).expect("Could not set the logger");
+    log::set_max_level(max_level);
+    CONSOLE_LEVEL.store(level as usize, Ordering::SeqCst);
+}
+
+/// Writes a progress event to stderr with `ProgressFormat::Json`, or draws it with
+/// `ProgressFormat::Tui`.
+pub fn event(event: Value) {
+    if JSON_EVENTS.load(Ordering::SeqCst) {
+        eprintln!("{}", event);
+    } else if TUI_EVENTS.load(Ordering::SeqCst) {
+        tui::handle(&event);
+    }
+}
+
+/// Returns true if progress is reported with events, i.e. JSON or the status display.
+pub fn emits_events() -> bool {
+    JSON_EVENTS.load(Ordering::SeqCst) || TUI_EVENTS.load(Ordering::SeqCst)
+}
+
+/// Returns true if messages at this level are printed to the terminal.
+pub fn prints(level: Level) -> bool {
+    level as usize <= CONSOLE_LEVEL.load(Ordering::SeqCst)
+}
+
+/// Colors the structure of a message: headings in bold, and the keys of `- key: value` lines by
+/// their depth, so nested output like the configuration and the summary is easier to scan.
+fn highlight(message: &str) -> String {
+    message
+        .lines()
+        .map(highlight_line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn highlight_line(line: &str) -> String {
+    let content = line.trim_left();
+    let indent = &line[..line.len() - content.len()];
+    if !content.starts_with("- ") {
+        return if content.ends_with(':') {
+            format!("{}{}", indent, Style::new().bold().paint(content))
+        } else {
+            line.to_string()
+        };
+    }
+    let item = &content[2..];
+    // Top-level items are indented once, under their heading.
+    let depth = (indent.len() / 2).saturating_sub(1);
+    let key = KEY_COLOURS[depth.min(KEY_COLOURS.len() - 1)];
+    let bullet = Colour::Fixed(8).paint("-");
+    match item.find(": ") {
+        Some(i) => format!("{}{} {}{}", indent, bullet, key.paint(&item[..i]), &item[i..]),
+        None if item.ends_with(':') => format!("{}{} {}", indent, bullet, key.paint(item)),
+        None => format!("{}{} {}", indent, bullet, item),
+    }
+}
+
+fn write_line(file: &Mutex<File>, level: Level, message: &str) {
+    let mut file = file.lock().unwrap();
+    // There's nowhere to report a failure to write the log.
+    let _ = writeln!(
+        file,
+        "{} {:<5} {}",
+        Local::now().format("%Y-%m-%d %H:%M:%S"),
+        level,
+        message
+    );
+}
+
+/// Returns the prefix of warnings and errors on the terminal.
+fn label(level: Level) -> &'static str {
+    if level == Level::Error {
+        "ERROR"
+    } else {
+        "WARNING"
+    }
+}
+
+impl ::std::str::FromStr for ProgressFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<ProgressFormat, String> {
+        match s {
+            "text" => Ok(ProgressFormat::Text),
+            "json" => Ok(ProgressFormat::Json),
+            "tui" => Ok(ProgressFormat::Tui),
+            _ => Err(format!("Invalid progress format (must be text, json, or tui): {}", s)),
+        }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if let Some(file) = self.file.as_ref() {
+            write_line(file, record.level(), &record.args().to_string());
+        }
+        if record.level() > self.level {
+            return;
+        }
+        match record.level() {
+            Level::Error if emits_events() => {
+                event(json!({"event": "error", "message": record.args().to_string()}))
+            }
+            Level::Warn if emits_events() => {
+                event(json!({"event": "warning", "message": record.args().to_string()}))
+            }
+            Level::Error | Level::Warn if COLOR.load(Ordering::SeqCst) => {
+                let colour = if record.level() == Level::Error {
+                    Colour::Red
+                } else {
+                    Colour::Yellow
+                };
+                eprintln!("{}: {}", colour.bold().paint(label(record.level())), record.args())
+            }
+            Level::Error | Level::Warn => eprintln!("{}: {}", label(record.level()), record.args()),
+            Level::Info | Level::Debug | Level::Trace if tui::is_drawing() => {}
+            Level::Info | Level::Debug | Level::Trace if COLOR.load(Ordering::SeqCst) => {
+                println!("{}", highlight(&record.args().to_string()))
+            }
+            Level::Info | Level::Debug | Level::Trace => println!("{}", record.args()),
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = self.file.as_ref() {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Precomputed camera projections, for `--projection-lut`.
+
+use riscan_pro::{CameraCalibration, Cmcs, Point};
+use std::f32;
+
+/// The result of looking up a direction in a projection lookup table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Lookup {
+    /// The interpolated pixel of the direction.
+    Pixel(f64, f64),
+    /// The direction doesn't project into the image.
+    Outside,
+    /// The direction is near the edge of the image, so it must be projected exactly.
+    Unknown,
+}
+
+/// The pixels of a grid of directions in a camera's coordinate system, by their horizontal and
+/// vertical angles from the camera's axis.
+///
+/// Pixels between grid points are interpolated bilinearly, so the error shrinks with the square of
+/// the grid's resolution. Grid cells whose corners don't all project into the image are left to
+/// the exact projection.
+#[derive(Clone, Debug)]
+pub struct ProjectionLut {
+    /// The horizontal and vertical angles of the first grid point, in radians.
+    origin: (f64, f64),
+    /// The distance between grid points, in radians.
+    step: f64,
+    columns: usize,
+    rows: usize,
+    /// The pixel of each grid point, row by row, or NaN if it doesn't project into the image.
+    pixels: Vec<[f32; 2]>,
+}
+
+impl ProjectionLut {
+    /// Tabulates `project`, which maps a point in the camera's coordinate system to a pixel, over
+    /// the camera calibration's angle extents every `resolution` degrees.
+    pub fn new<F>(
+        camera_calibration: &CameraCalibration,
+        resolution: f64,
+        project: F,
+    ) -> ProjectionLut
+    where
+        F: Fn(&Point<Cmcs>) -> Option<(f64, f64)>,
+    {
+        let step = resolution.to_radians();
+        let horz = (camera_calibration.tan_min_horz.atan(), camera_calibration.tan_max_horz.atan());
+        let vert = (camera_calibration.tan_min_vert.atan(), camera_calibration.tan_max_vert.atan());
+        let columns = ((horz.1 - horz.0) / step).ceil() as usize + 1;
+        let rows = ((vert.1 - vert.0) / step).ceil() as usize + 1;
+        let mut pixels = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            let tan_vert = (vert.0 + row as f64 * step).tan();
+            for column in 0..columns {
+                let tan_horz = (horz.0 + column as f64 * step).tan();
+                pixels.push(match project(&Point::cmcs(tan_horz, tan_vert, 1.)) {
+                    Some((u, v)) => [u as f32, v as f32],
+                    None => [f32::NAN, f32::NAN],
+                });
+            }
+        }
+        ProjectionLut {
+            origin: (horz.0, vert.0),
+            step: step,
+            columns: columns,
+            rows: rows,
+            pixels: pixels,
+        }
+    }
+
+    /// Returns the distance between grid points, in degrees.
+    pub fn resolution(&self) -> f64 {
+        self.step.to_degrees()
+    }
+
+    /// Looks up the pixel of a point in the camera's coordinate system.
+    ///
+    /// Points behind the camera, or outside of the grid, are outside of the image.
+    pub fn get(&self, cmcs: &Point<Cmcs>) -> Lookup {
+        if cmcs.is_behind_camera() {
+            return Lookup::Outside;
+        }
+        let column = (cmcs.tan_horz().atan() - self.origin.0) / self.step;
+        let row = (cmcs.tan_vert().atan() - self.origin.1) / self.step;
+        if column < 0. || row < 0. || column >= (self.columns - 1) as f64 ||
+            row >= (self.rows - 1) as f64
+        {
+            return Lookup::Outside;
+        }
+        let (i, j) = (column as usize, row as usize);
+        let (dx, dy) = (column - i as f64, row - j as f64);
+        let corners = [
+            self.pixels[j * self.columns + i],
+            self.pixels[j * self.columns + i + 1],
+            self.pixels[(j + 1) * self.columns + i],
+            self.pixels[(j + 1) * self.columns + i + 1],
+        ];
+        let outside = corners.iter().filter(|corner| corner[0].is_nan()).count();
+        if outside == corners.len() {
+            return Lookup::Outside;
+        } else if outside > 0 {
+            return Lookup::Unknown;
+        }
+        let interpolate = |k: usize| {
+            let top = f64::from(corners[0][k]) * (1. - dx) + f64::from(corners[1][k]) * dx;
+            let bottom = f64::from(corners[2][k]) * (1. - dx) + f64::from(corners[3][k]) * dx;
+            top * (1. - dy) + bottom * dy
+        };
+        Lookup::Pixel(interpolate(0), interpolate(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera_calibration() -> CameraCalibration {
+        CameraCalibration {
+            name: "camera".to_string(),
+            cx: 320.,
+            cy: 256.,
+            fx: 500.,
+            fy: 500.,
+            k1: 0.,
+            k2: 0.,
+            k3: 0.,
+            k4: 0.,
+            p1: 0.,
+            p2: 0.,
+            tan_max_horz: 0.6,
+            tan_max_vert: 0.5,
+            tan_min_horz: -0.6,
+            tan_min_vert: -0.5,
+            width: 640,
+            height: 512,
+        }
+    }
+
+    #[test]
+    fn matches_projection() {
+        let camera_calibration = camera_calibration();
+        let lut = ProjectionLut::new(&camera_calibration, 0.1, |cmcs| {
+            camera_calibration.cmcs_to_ics(cmcs)
+        });
+        for &(x, y, z) in &[(0., 0., 1.), (0.3, -0.2, 1.), (-2.5, 2., 5.), (0.123, 0.456, 2.)] {
+            let cmcs = Point::cmcs(x, y, z);
+            let (u, v) = camera_calibration.cmcs_to_ics(&cmcs).unwrap();
+            match lut.get(&cmcs) {
+                Lookup::Pixel(lut_u, lut_v) => {
+                    assert!((lut_u - u).abs() < 0.01, "u {} != {}", lut_u, u);
+                    assert!((lut_v - v).abs() < 0.01, "v {} != {}", lut_v, v);
+                }
+                lookup => panic!("{:?} looked up as {:?}", (x, y, z), lookup),
+            }
+        }
+    }
+
+    #[test]
+    fn outside() {
+        let camera_calibration = camera_calibration();
+        let lut = ProjectionLut::new(&camera_calibration, 0.1, |cmcs| {
+            camera_calibration.cmcs_to_ics(cmcs)
+        });
+        for &(x, y, z) in &[(0., 0., -1.), (2., 0., 1.), (0., -2., 1.)] {
+            assert_eq!(Lookup::Outside, lut.get(&Point::cmcs(x, y, z)));
+        }
+    }
+}
@@ -1,39 +1,204 @@
+extern crate ansi_term;
+extern crate atty;
+extern crate byteorder;
+extern crate chrono;
 #[macro_use]
 extern crate clap;
+extern crate crossbeam;
+extern crate flate2;
+extern crate glob;
+extern crate image;
+extern crate indicatif;
 extern crate irb;
 extern crate las;
+#[macro_use]
+extern crate lazy_static;
+#[macro_use]
+extern crate log;
+extern crate memmap;
+extern crate nalgebra;
 extern crate palette;
+extern crate png;
+extern crate regex;
 extern crate riscan_pro;
 extern crate scanifc;
 #[macro_use]
 extern crate serde_derive;
 #[macro_use]
+extern crate serde_json;
+extern crate sha2;
+#[macro_use]
 extern crate text_io;
 extern crate toml;
+extern crate yaml_rust;
+
+mod batch;
+mod bench;
+mod calibration;
+mod colorize;
+mod decoded;
+mod diff;
+mod distortion;
+mod dump;
+mod export;
+mod extra_bytes;
+mod footprint;
+mod frustum;
+mod geotiff;
+mod gradient;
+mod info;
+mod laz;
+mod logger;
+mod lut;
+mod matrix;
+mod memory;
+mod ortho;
+mod preview;
+mod project;
+mod ptx;
+mod quantile;
+mod raster;
+#[cfg(feature = "rdb")]
+mod rdb;
+mod rejections;
+mod report;
+mod retry;
+mod sha256;
+mod state;
+mod summary;
+mod tui;
+mod upload;
+mod utm;
 
-use clap::{App, ArgMatches};
+use batch::Affine;
+use chrono::{Duration, NaiveDateTime};
+use clap::{App, ArgMatches, Shell};
+use decoded::Decoded;
+use distortion::Distortion;
+use extra_bytes::{Attribute, DataType, Registry};
+use footprint::Footprint;
+use frustum::Frustum;
+use geotiff::GeoTiff;
+use glob::Pattern;
+use indicatif::{ProgressBar, ProgressStyle};
 use irb::Irb;
 use las::Color;
-use las::point::Format;
+use las::point::{Classification, Format};
+use log::{Level, LevelFilter};
+use logger::ProgressFormat;
+use lut::{Lookup, ProjectionLut};
+use memory::MemoryBudget;
+use nalgebra::{Point3, Projective3};
+use ortho::{Ortho, Statistic};
 use palette::{Gradient, Rgb};
-use riscan_pro::{CameraCalibration, MountCalibration, Point, Project, ScanPosition, Socs};
+use raster::{BadPixels, Mask, Raster};
+use regex::Regex;
+use retry::Retry;
+use riscan_pro::{CameraCalibration, Cmcs, Glcs, MountCalibration, Point, Project, ScanPosition,
+                 Socs};
 use riscan_pro::scan_position::Image;
 use scanifc::point3d::Stream;
+use state::State;
+use std::cell::Cell;
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::OsString;
 use std::fmt;
 use std::fs;
 use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
 use std::u16;
+use summary::{Checksum, Counts, Failure, Histogram, ScanPositionSummary, Summary, Throughput};
+use upload::Upload;
+use utm::UtmZone;
+use yaml_rust::Yaml;
+
+/// The number of points passed between the stages of the pipeline at a time, unless
+/// `--max-memory` is too small for them. The first chunk is also used to compute `--offset auto`.
+const CHUNK_LEN: usize = 10_000;
+
+/// The number of chunks that can wait between two stages of the pipeline before the earlier stage
+/// blocks.
+const PIPELINE_DEPTH: usize = 4;
+
+/// The capacity of each output file's buffer, unless `--max-memory` is too small for them.
+const WRITE_BUFFER_BYTES: usize = 1 << 20;
+
+/// The quantiles of each translation's temperatures that are logged as a suggested
+/// `--min-temperature` and `--max-temperature`, estimated as the points stream by.
+const RANGE_QUANTILES: (f64, f64) = (0.02, 0.98);
+
+/// Makes the names of decompressed files unique within this process.
+static TEMPORARY_FILES: AtomicUsize = AtomicUsize::new(0);
+
+/// How many points at the start of an rxp file are checked for pps-synced points, unless
+/// `--sync-to-pps` or `--no-sync-to-pps` is provided.
+const PPS_PROBE_POINTS: usize = 100_000;
+
+/// How many points are read between progress bar updates.
+const PROGRESS_INTERVAL: u64 = 10_000;
+
+/// Rough sizes of one point in the input formats that don't store a point count, used to
+/// estimate progress.
+const RXP_BYTES_PER_POINT: u64 = 8;
+const PTX_BYTES_PER_POINT: u64 = 40;
+const RDBX_BYTES_PER_POINT: u64 = 20;
+const LAZ_BYTES_PER_POINT: u64 = 6;
 
 fn main() {
     let yaml = load_yaml!("cli.yml");
-    let matches = App::from_yaml(yaml).get_matches();
-    print!("Configuring...");
-    std::io::stdout().flush().unwrap();
+    let matches = App::from_yaml(yaml).get_matches_from(args(yaml));
+    if let Some(matches) = matches.subcommand_matches("completions") {
+        let shell = value_t!(matches, "SHELL", Shell).unwrap();
+        App::from_yaml(yaml).gen_completions_to("tce", shell, &mut std::io::stdout());
+        return;
+    }
+    logger::init(
+        log_level(&matches),
+        matches.value_of("log-file").map(Path::new),
+        value_t!(matches, "progress-format", ProgressFormat).unwrap(),
+        // Messages go to stdout, and warnings and errors to stderr.
+        !matches.is_present("no-color") && std::env::var_os("NO_COLOR").is_none() &&
+            atty::is(atty::Stream::Stdout) && atty::is(atty::Stream::Stderr),
+    );
+    if let Some(matches) = matches.subcommand_matches("diff") {
+        diff::diff(matches);
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("check-calibration") {
+        calibration::check(matches);
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("preview-gradient") {
+        gradient::preview(matches);
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("irb") {
+        if let Some(matches) = matches.subcommand_matches("info") {
+            info::irb(matches);
+        }
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("rxp") {
+        if let Some(matches) = matches.subcommand_matches("info") {
+            info::rxp(matches);
+        }
+        return;
+    }
+    if let Some(matches) = matches.subcommand_matches("project") {
+        if let Some(matches) = matches.subcommand_matches("info") {
+            info::project(matches);
+        }
+        return;
+    }
+    info!("Configuring...");
     let config = Config::new(&matches);
-    println!("done.");
-    println!("{}", config);
-    loop {
+    info!("{}", config);
+    let asks = asks_to_continue(&matches);
+    while asks {
         print!("Continue? (y/n) ");
         std::io::stdout().flush().unwrap();
         let answer: String = read!();
@@ -45,52 +210,714 @@ fn main() {
         }
     }
 
+    if let Some(format) = matches.value_of("dump-config") {
+        let path = config.las_dir.join(format!("tce-config.{}", format));
+        info!("Writing configuration to {}", path.display());
+        dump::write(&path, &dump::options(yaml, &matches));
+        config.publish(&path);
+    }
+    if let (Some(path), false) = (config.kml.as_ref(), config.bench) {
+        info!("Writing scan positions and camera poses to {}", path.display());
+        config.write_kml(path);
+        config.publish(path);
+    }
+    let mut merged_ortho = None;
+    let mut footprints = Vec::new();
+    let mut state = matches.value_of("state-file").map(|path| {
+        State::from_path(path).expect(&format!("Could not read state file {}", path))
+    });
+    let mut summary = Summary::default();
+    let start = Instant::now();
+    run_started(&config, &config.scan_positions());
     for scan_position in config.scan_positions() {
-        println!("Colorizing {}:", scan_position.name);
-        let translations = config.translations(scan_position);
-        if translations.is_empty() {
-            println!("  - No translations found");
-        } else {
-            for translation in translations {
-                println!(
-                    "  - Translation:\n    - Infile: {}\n    - Outfile: {}",
+        let footprint = colorize_scan_position(
+            &config,
+            scan_position,
+            matches.is_present("keep-going"),
+            &mut summary,
+            &mut merged_ortho,
+            &mut state,
+        );
+        footprints.push((scan_position, footprint));
+    }
+    summary.seconds = seconds(start);
+    summary.unlinked_images = config.unlinked_images();
+    summary.unreadable_images = config.unreadable_images();
+    if let (Some(path), false) = (config.geojson.as_ref(), config.bench) {
+        info!("Writing footprints to {}", path.display());
+        config.write_geojson(path, &footprints);
+        summary.checksums.extend(config.checksum(path));
+        config.publish(path);
+    }
+    if let Some(ortho) = merged_ortho {
+        let path = config.las_dir.join("ortho.tif");
+        info!("Writing merged orthomosaic to {}", path.display());
+        config.write_ortho(&ortho, &path);
+        summary.checksums.extend(config.checksum(&path));
+        config.publish(&path);
+    }
+    // Before the final messages, so they're printed below the status display.
+    logger::event(json!({"event": "complete", "summary": summary}));
+    info!("Complete!");
+    info!("{}", summary);
+    if let Some(path) = matches.value_of("summary") {
+        summary.write_to_path(path).expect(&format!("Could not write summary {}", path));
+        config.publish(Path::new(path));
+    }
+    if let Some(path) = matches.value_of("report") {
+        info!("Writing report to {}", path);
+        report::write(path, &config.to_string(), &summary)
+            .expect(&format!("Could not write report {}", path));
+        config.publish(Path::new(path));
+    }
+    // After the report, which embeds them.
+    publish_previews(&config, &summary);
+    if matches.is_present("watch") {
+        let interval = value_t!(matches, "watch-interval", f64).unwrap();
+        watch(
+            &config,
+            std::time::Duration::from_millis((interval * 1000.) as u64),
+            matches.is_present("keep-going"),
+            &mut state,
+        );
+    }
+    if !summary.failures.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Emits the `run_started` progress event for a run that colorizes these scan positions.
+fn run_started(config: &Config, scan_positions: &[&ScanPosition]) {
+    if logger::emits_events() {
+        logger::event(json!({
+            "event": "run_started",
+            "scan_positions": scan_positions
+                .iter()
+                .map(|scan_position| scan_position.name.clone())
+                .collect::<Vec<_>>(),
+            "estimated_points": scan_positions
+                .iter()
+                .flat_map(|scan_position| config.translations(scan_position))
+                .map(|translation| config.estimated_point_count(&translation.infile))
+                .sum::<u64>(),
+        }));
+    }
+}
+
+/// Returns the message of a caught panic.
+fn panic_message(payload: &(std::any::Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown error".to_string()
+    }
+}
+
+/// Colorizes all of a scan position's translations, adding them to the summary and merging their
+/// orthomosaics into `merged_ortho` if `--merge-ortho` was provided.
+///
+/// With a `--state-file`, translations whose inputs haven't changed since they were last colorized
+/// are skipped.
+///
+/// Returns the footprint of the whole scan position.
+fn colorize_scan_position(
+    config: &Config,
+    scan_position: &ScanPosition,
+    keep_going: bool,
+    summary: &mut Summary,
+    merged_ortho: &mut Option<Ortho>,
+    state: &mut Option<State>,
+) -> Footprint {
+    let scan_position_start = Instant::now();
+    let mut counts = Counts::default();
+    info!("Colorizing {}:", scan_position.name);
+    logger::event(json!({
+        "event": "scan_position_started",
+        "scan_position": scan_position.name,
+    }));
+    let mut footprint = Footprint::default();
+    let mut histogram = config.histogram();
+    let mut previews = Vec::new();
+    let mut throughputs = Vec::new();
+    let translations = config.translations(scan_position);
+    let number_of_translations = translations.len();
+    if translations.is_empty() {
+        info!("  - No translations found");
+    } else {
+        for translation in translations {
+            info!(
+                "  - Translation:\n    - Infile: {}\n    - Outfile: {}",
+                translation.infile.display(),
+                translation.outfile.display()
+            );
+            if let Some(epoch) = translation.epoch.as_ref() {
+                info!("    - Epoch: {}", epoch);
+            }
+            if let Some(rgb_outfile) = translation.rgb_outfile.as_ref() {
+                info!("    - RGB outfile: {}", rgb_outfile.display());
+            }
+            for split_outfile in &translation.split_outfiles {
+                info!("    - Split outfile: {}", split_outfile.display());
+            }
+            let fingerprint = state
+                .as_ref()
+                .map(|_| config.input_fingerprint(scan_position, &translation));
+            if let (Some(state), Some(fingerprint)) = (state.as_ref(), fingerprint.as_ref()) {
+                let is_current = state.is_current(&translation.infile, fingerprint);
+                // Uploaded outputs aren't kept locally.
+                let has_outfile = translation.outfile.exists() || config.upload.is_some();
+                if is_current && has_outfile {
+                    info!("    - Unchanged since it was last colorized, skipping");
+                    logger::event(json!({
+                        "event": "translation_skipped",
+                        "scan_position": scan_position.name,
+                        "infile": translation.infile.display().to_string(),
+                    }));
+                    continue;
+                }
+            }
+            logger::event(json!({
+                "event": "translation_started",
+                "scan_position": scan_position.name,
+                "infile": translation.infile.display().to_string(),
+                "outfile": translation.outfile.display().to_string(),
+            }));
+            let translation_start = Instant::now();
+            let colorization = if keep_going {
+                match panic::catch_unwind(AssertUnwindSafe(
+                    || config.colorize(scan_position, &translation, keep_going),
+                )) {
+                    Ok(colorization) => colorization,
+                    Err(payload) => {
+                        let message = panic_message(payload.as_ref());
+                        error!(
+                            "could not colorize {}, continuing: {}",
+                            translation.infile.display(),
+                            message
+                        );
+                        summary.failures.push(Failure {
+                            scan_position: scan_position.name.clone(),
+                            infile: translation.infile.display().to_string(),
+                            message: message,
+                        });
+                        continue;
+                    }
+                }
+            } else {
+                config.colorize(scan_position, &translation, keep_going)
+            };
+            // Truncated outputs are colorized again on the next run.
+            if let Some(message) = colorization.read_error.as_ref() {
+                error!(
+                    "could not read all of {} past point {}, continuing: {}",
                     translation.infile.display(),
-                    translation.outfile.display()
+                    colorization.counts.read,
+                    message
                 );
-                config.colorize(scan_position, &translation);
+                summary.failures.push(Failure {
+                    scan_position: scan_position.name.clone(),
+                    infile: translation.infile.display().to_string(),
+                    message: message.clone(),
+                });
+            }
+            if let (Some(state), Some(fingerprint), false, None) = (
+                state.as_mut(),
+                fingerprint,
+                config.bench,
+                colorization.read_error.as_ref(),
+            ) {
+                state.record(&translation.infile, fingerprint).expect(&format!(
+                    "Could not write state file after colorizing {}",
+                    translation.infile.display()
+                ));
+            }
+            let throughput = Throughput::new(
+                &translation.infile,
+                &translation.outfile,
+                &colorization.counts,
+                seconds(translation_start),
+            );
+            info!("    - {}", throughput);
+            logger::event(json!({
+                "event": "throughput",
+                "infile": throughput.infile,
+                "seconds": throughput.seconds,
+                "read_per_second": throughput.read_per_second,
+                "written_per_second": throughput.written_per_second,
+            }));
+            throughputs.push(throughput);
+            footprint.merge(&colorization.footprint);
+            counts.add(&colorization.counts);
+            histogram.merge(&colorization.histogram);
+            previews.extend(
+                colorization
+                    .preview
+                    .map(|preview| preview.display().to_string()),
+            );
+            summary.checksums.extend(colorization.checksums);
+            if let (true, Some(ortho)) = (config.merge_ortho, colorization.ortho) {
+                merged_ortho
+                    .get_or_insert_with(|| Ortho::new(config.ortho_resolution.unwrap()))
+                    .merge(&ortho);
+            }
+        }
+    }
+    summary.add(ScanPositionSummary {
+        name: scan_position.name.clone(),
+        translations: number_of_translations,
+        counts: counts,
+        seconds: seconds(scan_position_start),
+        histogram: histogram,
+        previews: previews,
+        throughputs: throughputs,
+    });
+    footprint
+}
+
+/// Checks the imagery of every scan position every `interval`, and colorizes the scan positions
+/// whose imagery has changed, forever.
+///
+/// Merged outputs, e.g. the geojson footprints and the merged orthomosaic, aren't updated.
+fn watch(
+    config: &Config,
+    interval: std::time::Duration,
+    keep_going: bool,
+    state: &mut Option<State>,
+) {
+    let mut fingerprints = config
+        .scan_positions()
+        .into_iter()
+        .map(|scan_position| {
+            (scan_position.name.clone(), config.imagery_fingerprint(scan_position))
+        })
+        .collect::<HashMap<_, _>>();
+    info!("Watching for new imagery every {:?}...", interval);
+    loop {
+        std::thread::sleep(interval);
+        for scan_position in config.scan_positions() {
+            let fingerprint = config.imagery_fingerprint(scan_position);
+            if fingerprints.get(&scan_position.name) == Some(&fingerprint) {
+                continue;
             }
+            info!("Imagery changed for {}", scan_position.name);
+            // Each colorization is its own run, with its own progress.
+            run_started(config, &[scan_position]);
+            let mut summary = Summary::default();
+            colorize_scan_position(
+                config,
+                scan_position,
+                keep_going,
+                &mut summary,
+                &mut None,
+                state,
+            );
+            summary.unlinked_images = config.unlinked_images();
+            summary.unreadable_images = config.unreadable_images();
+            info!("{}", summary);
+            logger::event(json!({"event": "complete", "summary": summary}));
+            publish_previews(config, &summary);
+            fingerprints.insert(scan_position.name.clone(), fingerprint);
+        }
+    }
+}
+
+/// Uploads the `--preview` images of a run, if there's an `--output-url`.
+fn publish_previews(config: &Config, summary: &Summary) {
+    for scan_position in &summary.scan_positions {
+        for preview in &scan_position.previews {
+            config.publish(Path::new(preview));
+        }
+    }
+}
+
+/// Returns the seconds since an instant.
+fn seconds(instant: Instant) -> f64 {
+    let elapsed = instant.elapsed();
+    elapsed.as_secs() as f64 + elapsed.subsec_nanos() as f64 * 1e-9
+}
+
+/// Returns the command line arguments, with any flags that are set by `TCE_*` environment
+/// variables added.
+///
+/// Clap reads options from the environment itself, but giving a flag an `env` makes it take a
+/// value, so flags are resolved here instead. A flag is set by "1", "true", or "yes" and left unset
+/// by "0", "false", or "no", and any other value is an error. It's also left alone if it, or a flag
+/// that it conflicts with, is already on the command line.
+fn args(yaml: &Yaml) -> Vec<OsString> {
+    let command_line = std::env::args_os().collect::<Vec<_>>();
+    let flags = yaml["args"]
+        .as_vec()
+        .expect("cli.yml has no args")
+        .iter()
+        .filter_map(|arg| {
+            let (name, settings) = arg.as_hash()
+                .and_then(|hash| hash.iter().next())
+                .expect("invalid arg in cli.yml");
+            if settings["takes_value"].as_bool().unwrap_or(false) {
+                None
+            } else {
+                settings["long"].as_str().map(|long| (name.as_str().unwrap_or(long), settings))
+            }
+        })
+        .collect::<Vec<_>>();
+    let is_on_command_line = |name: &str| {
+        let settings = flags
+            .iter()
+            .find(|&&(flag, _)| flag == name)
+            .map(|&(_, settings)| settings);
+        let long = settings.and_then(|settings| settings["long"].as_str());
+        let short = settings.and_then(|settings| settings["short"].as_str());
+        command_line
+            .iter()
+            .skip(1)
+            .filter_map(|arg| arg.to_str())
+            .take_while(|&arg| arg != "--")
+            .any(|arg| if arg.starts_with("--") {
+                long.map(|long| arg[2..] == *long).unwrap_or(false)
+            } else if arg.starts_with('-') && arg[1..].chars().all(char::is_alphabetic) {
+                // Short flags can be combined, e.g. -qy, and repeated, e.g. -vv.
+                short.map(|short| arg[1..].contains(short)).unwrap_or(false)
+            } else {
+                false
+            })
+    };
+    let mut args = command_line.clone();
+    for &(name, settings) in &flags {
+        let long = settings["long"].as_str().expect("flags have a long name");
+        let var = format!("TCE_{}", long.to_uppercase().replace('-', "_"));
+        let is_set = match std::env::var(&var) {
+            Ok(value) => {
+                let is_any = |values: &[&str]| {
+                    values
+                        .iter()
+                        .any(|v| value.trim().eq_ignore_ascii_case(v))
+                };
+                if is_any(&["1", "true", "yes"]) {
+                    true
+                } else if is_any(&["0", "false", "no"]) {
+                    false
+                } else {
+                    panic!(
+                        "Invalid {}={:?}: use 1, true, or yes to set --{}, or 0, false, or no",
+                        var,
+                        value,
+                        long
+                    );
+                }
+            }
+            Err(std::env::VarError::NotPresent) => false,
+            Err(std::env::VarError::NotUnicode(_)) => {
+                panic!("Invalid {}: the value isn't unicode", var)
+            }
+        };
+        let conflicts_with_command_line = flags
+            .iter()
+            .filter(|&&(other, other_settings)| {
+                settings["conflicts_with"].as_str() == Some(other) ||
+                    other_settings["conflicts_with"].as_str() == Some(name)
+            })
+            .any(|&(other, _)| is_on_command_line(other));
+        if is_set && !is_on_command_line(name) && !conflicts_with_command_line {
+            args.insert(1, format!("--{}", long).into());
+        }
+    }
+    args
+}
+
+/// Returns true if we should ask before colorizing.
+///
+/// We don't ask with `--yes` or `--quiet`, when stdin isn't a terminal (e.g. in a pipeline) or
+/// has the scan position names, or when progress isn't reported as text.
+fn asks_to_continue(matches: &ArgMatches) -> bool {
+    let names_from_stdin = matches
+        .values_of("scan-position")
+        .map(|mut values| values.any(|value| value == "-"))
+        .unwrap_or(false);
+    !matches.is_present("yes") && !matches.is_present("quiet") && !names_from_stdin &&
+        atty::is(atty::Stream::Stdin) &&
+        value_t!(matches, "progress-format", ProgressFormat).unwrap() == ProgressFormat::Text
+}
+
+/// Returns the log level of the `--quiet` and `--verbose` flags.
+fn log_level(matches: &ArgMatches) -> LevelFilter {
+    if matches.is_present("quiet") {
+        LevelFilter::Error
+    } else {
+        match matches.occurrences_of("verbose") {
+            0 => LevelFilter::Info,
+            1 => LevelFilter::Debug,
+            _ => LevelFilter::Trace,
         }
     }
-    println!("Complete!");
 }
 
 struct Config {
+    atmosphere: Option<Atmosphere>,
+    bad_pixel_mode: BadPixelMode,
+    bad_pixels: HashMap<String, BadPixels>,
+    bench: bool,
+    camera_calibration_files: Vec<PathBuf>,
+    cameras: Vec<String>,
+    checksum_sidecars: bool,
+    checksums: bool,
+    coverage: bool,
+    delta_dir: Option<PathBuf>,
+    distortions: HashMap<String, Distortion>,
+    dual_output: bool,
+    emissivity: f64,
+    emissivity_map: EmissivityMap,
+    epochs: Vec<Epoch>,
+    excluded_images: Vec<Pattern>,
+    excluded_scan_positions: Vec<String>,
+    flag_without_thermal: Option<Flag>,
+    flat_fields: HashMap<String, Raster>,
+    geojson: Option<PathBuf>,
     image_dir: PathBuf,
+    image_extensions: Vec<String>,
+    image_ids: bool,
+    image_layout: String,
+    image_map: Option<Vec<ImageMapEntry>>,
+    image_stats: bool,
+    image_time_format: Option<String>,
+    input_dir: Option<PathBuf>,
+    input_frame: Frame,
     keep_without_thermal: bool,
+    kml: Option<PathBuf>,
     las_dir: PathBuf,
+    las_version: (u8, u8),
+    laz: bool,
+    laz_chunk_size: Option<u32>,
+    mask_dir: Option<PathBuf>,
     max_reflectance: f32,
+    max_temperature: f32,
+    /// The largest difference between a point's temperatures for them to be averaged.
+    max_temperature_spread: Option<f64>,
+    max_time_offset: Option<Duration>,
+    memory: MemoryBudget,
+    merge_ortho: bool,
+    mmap_images: bool,
+    /// The number of temperatures a point needs to be colorized.
+    min_images: usize,
     min_reflectance: f32,
+    min_temperature: f32,
+    nir_temperature: Option<(f32, f32)>,
+    nodata_value: NodataValue,
+    number_scans: bool,
+    offset: Offset,
+    only_images: Vec<Pattern>,
+    orientation: Orientation,
+    ortho_resolution: Option<f64>,
+    ortho_statistic: Statistic,
+    output_layout: OutputLayout,
+    point_format: u8,
+    pop_overridden: bool,
+    position_rotations: HashMap<String, Rotation>,
+    preview_width: Option<usize>,
     project: Project,
-    rotate: bool,
+    /// With `--projection-lut`, the projection of each camera calibration, by name.
+    projection_luts: HashMap<String, ProjectionLut>,
+    reflected_temperature: f64,
+    rejections: bool,
+    retry: Retry,
+    rgb_dir: Option<PathBuf>,
+    scale: f64,
+    scan_position_aliases: HashMap<String, String>,
     scan_position_names: Option<Vec<String>>,
-    sync_to_pps: bool,
+    scan_time_format: String,
+    scans: Vec<Pattern>,
+    skip_points: usize,
+    sop_file: Option<PathBuf>,
+    split_by_temperature: Vec<f64>,
+    /// `None` means we check each rxp file for pps-synced points.
+    sync_to_pps: Option<bool>,
     temperature_gradient: Gradient<Rgb>,
+    thermal_orthos: Vec<(PathBuf, GeoTiff)>,
+    tilt_images: Vec<Pattern>,
+    tilt_mount: Option<String>,
+    time_window: Option<TimeWindow>,
+    timings: bool,
+    transform: Option<(PathBuf, Projective3<f64>)>,
+    /// Image files that aren't any of their scan position's images, and were left out of it.
+    unlinked_images: Mutex<BTreeSet<PathBuf>>,
+    /// Images that couldn't be opened, and were left out of their scan positions.
+    unreadable_images: Mutex<BTreeSet<PathBuf>>,
+    upload: Option<Upload>,
     use_scanpos_names: bool,
+    /// The range of recorded pixel temperatures, in °C, that are used.
+    valid_temperatures: (f64, f64),
+    utm_zone: Option<UtmZone>,
     name_map: NameMap,
 }
 
 struct ImageGroup<'a> {
+    atmosphere: Option<Atmosphere>,
+    bad_pixel_mode: BadPixelMode,
+    bad_pixels: Option<&'a BadPixels>,
     camera_calibration: &'a CameraCalibration,
+    /// The image's temperatures, once they've been decoded with `preload`.
+    decoded: Option<Decoded>,
+    distortion: Option<&'a Distortion>,
+    emissivity: f64,
+    flat_field: Option<&'a Raster>,
+    frustum: Frustum,
     image: &'a Image,
     irb: Irb,
     irb_path: PathBuf,
+    mask: Option<Mask>,
+    mount_calibration: &'a MountCalibration,
+    orientation: Orientation,
+    projection_lut: Option<&'a ProjectionLut>,
+    reflected_temperature: f64,
+    /// The transform from the scanner's own coordinate system to the camera's.
+    socs_to_cmcs: Affine,
+    /// The range of recorded pixel temperatures, in °C, that are used.
+    valid_temperatures: (f64, f64),
+    // Declared after `irb` so the decompressed image is only removed once it is closed.
+    #[allow(dead_code)]
+    temporary_file: Option<TemporaryFile>,
+}
+
+/// A true-color photograph, e.g. from a camera mounted on the scanner.
+struct RgbImageGroup<'a> {
+    camera_calibration: &'a CameraCalibration,
+    image: &'a Image,
     mount_calibration: &'a MountCalibration,
-    rotate: bool,
+    path: PathBuf,
+    rgb: image::RgbImage,
+}
+
+/// A file that is removed when dropped.
+struct TemporaryFile(PathBuf);
+
+/// What we learned about the points while colorizing a scan.
+struct Colorization {
+    checksums: Vec<Checksum>,
+    counts: Counts,
+    footprint: Footprint,
+    histogram: Histogram,
+    ortho: Option<Ortho>,
+    preview: Option<PathBuf>,
+    /// With `--keep-going`, why the input file couldn't be read to its end.
+    read_error: Option<String>,
 }
 
 struct Translation {
+    /// The imagery epoch, if more than one was provided with `--epoch`.
+    epoch: Option<String>,
+    image_dir: PathBuf,
     infile: PathBuf,
     outfile: PathBuf,
+    /// With `--dual-output`, the file that gets the RGB-colored copy of the points.
+    rgb_outfile: Option<PathBuf>,
+    /// With `--split-by-temperature`, the files that get the points in each temperature range,
+    /// coldest first.
+    split_outfiles: Vec<PathBuf>,
+}
+
+/// How the image files are oriented relative to the images in the RiSCAN Pro project.
+#[derive(Clone, Copy, Debug, Default)]
+struct Orientation {
+    flip_horizontal: bool,
+    flip_vertical: bool,
+    rotation: Rotation,
+}
+
+/// A point to colorize, from any of the input formats.
+struct ScanPoint {
+    socs: Point<Socs>,
+    intensity: u16,
+    /// The time of the point in seconds, if the input format records one.
+    time: Option<f64>,
+}
+
+/// A colorized point on its way to the output files.
+struct OutputPoint {
+    /// The point for the output file, and its `--split-by-temperature` file.
+    point: las::Point,
+    /// With `--dual-output`, the RGB-colored copy of the point.
+    rgb_point: Option<las::Point>,
+    /// The index of the `--split-by-temperature` file that gets the point, if any.
+    split_index: Option<usize>,
+}
+
+/// The coordinate system of the points in an input file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Frame {
+    Socs,
+    Prcs,
+    Glcs,
+}
+
+/// How the offsets of the output las files are chosen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Offset {
+    /// The project's origin, in the output coordinate system.
+    Pop,
+    /// The minimum of the first points of each file, rounded down to the nearest meter.
+    Auto,
+    /// Fixed values.
+    Values(f64, f64, f64),
+}
+
+/// How points without thermal data are marked in the las output.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Flag {
+    /// The withheld bit.
+    Withheld,
+    /// The synthetic bit.
+    Synthetic,
+    /// A classification, e.g. a user-definable class.
+    Classification(Classification),
+}
+
+/// The times of the points to colorize, in seconds, inclusive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct TimeWindow {
+    start: f64,
+    end: f64,
+}
+
+/// The temperature written for points that are kept without thermal data.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum NodataValue {
+    /// NaN, which some readers can't handle in gps time.
+    Nan,
+    /// A sentinel value, e.g. -9999.
+    Value(f64),
+    /// No temperatures are written to gps time at all.
+    OmitField,
+}
+
+/// Where the output files go in the las directory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum OutputLayout {
+    /// All files directly in the las directory.
+    Flat,
+    /// Each scan position's files in a `<las dir>/<scan position>` subdirectory.
+    PerScanPosition,
+}
+
+/// A clockwise rotation of the image files.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Rotation {
+    R0,
+    R90,
+    R180,
+    R270,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum BadPixelMode {
+    Inpaint,
+    Skip,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Atmosphere {
+    relative_humidity: f64,
+    temperature: f64,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -104,25 +931,88 @@ struct FromTo {
     to: String,
 }
 
+/// A named set of images of the same scan positions, e.g. from one time of day.
+#[derive(Debug)]
+struct Epoch {
+    name: String,
+    image_dir: PathBuf,
+}
+
+#[derive(Debug)]
+struct ImageMapEntry {
+    path: PathBuf,
+    scan_position: String,
+    image: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct EmissivityMap {
+    emissivities: Vec<Emissivity>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Emissivity {
+    scan_position: String,
+    image: Option<String>,
+    emissivity: f64,
+}
+
 impl Config {
     fn new(matches: &ArgMatches) -> Config {
         use std::fs::File;
         use std::io::Read;
         use toml;
 
-        let project = Project::from_path(matches.value_of("PROJECT").unwrap()).unwrap();
+        let mut project = project::open(matches.value_of("PROJECT").unwrap());
+        if let Some(pop) = matches.value_of("pop") {
+            project.pop = if Path::new(pop).is_file() {
+                let mut s = String::new();
+                File::open(pop).unwrap().read_to_string(&mut s).unwrap();
+                matrix::parse(&s).expect(&format!("Invalid pop in {}", pop))
+            } else {
+                matrix::parse(pop).expect(&format!("Invalid --pop: {}", pop))
+            };
+        }
+        if let Some(sop_file) = matches.value_of("sop-file") {
+            let sops = matrix::read_named(sop_file).expect(&format!(
+                "Could not read sop file {}",
+                sop_file
+            ));
+            for (name, sop) in sops {
+                project
+                    .scan_positions
+                    .get_mut(&name)
+                    .expect(&format!("--sop-file: no scan position named {}", name))
+                    .sop = sop;
+            }
+        }
+        override_camera_calibrations(matches, &mut project);
+        let cameras = matches
+            .values_of("camera")
+            .map(|values| values.map(|value| value.to_string()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        for camera in &cameras {
+            if !project.camera_calibrations.contains_key(camera) {
+                let mut names = project
+                    .camera_calibrations
+                    .keys()
+                    .map(|name| name.as_str())
+                    .collect::<Vec<_>>();
+                names.sort();
+                panic!(
+                    "--camera: no camera calibration named {} in the project (expected one of: {})",
+                    camera,
+                    names.join(", ")
+                );
+            }
+        }
         let image_dir = PathBuf::from(matches.value_of("IMAGE_DIR").unwrap());
         let las_dir = Path::new(matches.value_of("LAS_DIR").unwrap()).to_path_buf();
         let min_reflectance = value_t!(matches, "min-reflectance", f32).unwrap();
         let max_reflectance = value_t!(matches, "max-reflectance", f32).unwrap();
         let min_temperature = value_t!(matches, "min-temperature", f32).unwrap();
         let max_temperature = value_t!(matches, "max-temperature", f32).unwrap();
-        let min_temperature_color = Rgb::new(0.0, 0., 1.0);
-        let max_temperature_color = Rgb::new(1.0, 0., 0.);
-        let temperature_gradient = Gradient::with_domain(vec![
-            (min_temperature, min_temperature_color),
-            (max_temperature, max_temperature_color),
-        ]);
+        let temperature_gradient = gradient::temperature_gradient(min_temperature, max_temperature);
         let name_map = if let Some(name_map) = matches.value_of("name-map") {
             let mut s = String::new();
             File::open(name_map)
@@ -133,104 +1023,990 @@ impl Config {
         } else {
             NameMap::default()
         };
-        Config {
+        let emissivity_map = if let Some(emissivity_map) = matches.value_of("emissivity-map") {
+            let mut s = String::new();
+            File::open(emissivity_map)
+                .unwrap()
+                .read_to_string(&mut s)
+                .unwrap();
+            toml::from_str(&s).unwrap()
+        } else {
+            EmissivityMap::default()
+        };
+        let scan_position_aliases = if let Some(path) = matches.value_of("scan-position-aliases") {
+            let mut s = String::new();
+            File::open(path).unwrap().read_to_string(&mut s).unwrap();
+            let aliases: HashMap<String, String> = toml::from_str(&s)
+                .expect(&format!("Invalid scan position aliases in {}", path));
+            for name in aliases.keys() {
+                if !project.scan_positions.contains_key(name) {
+                    panic!("--scan-position-aliases: no scan position named {}", name);
+                }
+            }
+            aliases
+        } else {
+            HashMap::new()
+        };
+        let flat_fields = matches
+            .value_of("flat-field-dir")
+            .map(|dir| per_camera(dir, |path| Raster::from_path(path)))
+            .unwrap_or_default();
+        let bad_pixels = matches
+            .value_of("bad-pixel-dir")
+            .map(|dir| per_camera(dir, |path| BadPixels::from_path(path)))
+            .unwrap_or_default();
+        let distortions = matches
+            .value_of("distortion-dir")
+            .map(|dir| per_camera(dir, |path| Distortion::from_path(path)))
+            .unwrap_or_default();
+        let projection_luts = matches
+            .value_of("projection-lut")
+            .map(|_| {
+                let resolution = value_t!(matches, "projection-lut", f64).unwrap();
+                if resolution <= 0. {
+                    panic!("--projection-lut must be positive, got {}", resolution);
+                }
+                project
+                    .camera_calibrations
+                    .values()
+                    .map(|camera_calibration| {
+                        let distortion = distortions.get(&camera_calibration.name);
+                        let lut = ProjectionLut::new(camera_calibration, resolution, |cmcs| {
+                            project_cmcs(cmcs, camera_calibration, distortion)
+                        });
+                        (camera_calibration.name.clone(), lut)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let bad_pixel_mode = match matches.value_of("bad-pixel-mode").unwrap() {
+            "inpaint" => BadPixelMode::Inpaint,
+            "skip" => BadPixelMode::Skip,
+            mode => panic!("Unknown bad pixel mode: {}", mode),
+        };
+        let position_rotations = position_rotations(matches, &project);
+        let las_version = match matches.value_of("las-version").unwrap() {
+            "1.2" => (1, 2),
+            "1.4" => (1, 4),
+            version => panic!("Unsupported las version: {}", version),
+        };
+        let mut point_format = value_t!(matches, "point-format", u8).unwrap();
+        if Format::new(point_format).is_err() {
+            panic!("Invalid point format: {}", point_format);
+        }
+        let nodata_value = value_t!(matches, "nodata-value", NodataValue).unwrap();
+        if nodata_value == NodataValue::OmitField {
+            // Every point in a las file has the same fields, so we drop gps time from the format.
+            point_format = match point_format {
+                0 | 2 => point_format,
+                1 => 0,
+                3 => 2,
+                _ => panic!(
+                    "--nodata-value omit-field requires point format 0 through 3, not {}",
+                    point_format
+                ),
+            };
+        }
+        if point_format > 5 && las_version < (1, 4) {
+            panic!("Point format {} requires --las-version 1.4", point_format);
+        }
+        if matches.is_present("laz-chunk-size") && !matches.is_present("laz") {
+            panic!("--laz-chunk-size requires --laz");
+        }
+        let nir_temperature = if matches.is_present("nir-temperature") {
+            if !Format::new(point_format).unwrap().has_nir {
+                panic!("--nir-temperature requires a point format with NIR, e.g. 8");
+            }
+            Some((min_temperature, max_temperature))
+        } else {
+            None
+        };
+        let split_by_temperature = matches
+            .value_of("split-by-temperature")
+            .map(|s| {
+                let breaks = s.split(',')
+                    .map(|value| value.trim().parse::<f64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .expect(&format!("Invalid temperature breaks: {}", s));
+                if breaks.windows(2).any(|pair| pair[0] >= pair[1]) {
+                    panic!("Temperature breaks must be increasing: {}", s);
+                }
+                breaks
+            })
+            .unwrap_or_default();
+        if matches.is_present("flag-without-thermal") &&
+            !matches.is_present("keep-without-thermal")
+        {
+            panic!("--flag-without-thermal requires --keep-without-thermal");
+        }
+        if matches.is_present("kml") && !matches.is_present("utm-zone") {
+            panic!("--kml requires --utm-zone");
+        }
+        if matches.is_present("merge-ortho") && !matches.is_present("ortho") {
+            panic!("--merge-ortho requires --ortho");
+        }
+        if matches.is_present("dual-output") && !matches.is_present("rgb-dir") {
+            panic!("--dual-output requires --rgb-dir");
+        }
+        let image_layout = if matches.is_present("recursive") {
+            if matches.occurrences_of("image-layout") > 0 {
+                panic!("--recursive can't be combined with --image-layout, use ** in the layout");
+            }
+            "{root}/{scanpos}/**/*".to_string()
+        } else {
+            matches.value_of("image-layout").unwrap().to_string()
+        };
+        let max_time_offset = matches.value_of("max-time-offset").map(|minutes| {
+            let minutes = minutes.parse::<f64>().expect(&format!(
+                "Invalid --max-time-offset: {}",
+                minutes
+            ));
+            Duration::milliseconds((minutes * 60_000.) as i64)
+        });
+        if max_time_offset.is_some() && !matches.is_present("image-time-format") {
+            panic!("--max-time-offset requires --image-time-format");
+        }
+        let atmosphere = if matches.is_present("atmospheric-correction") {
+            Some(Atmosphere {
+                relative_humidity: value_t!(matches, "relative-humidity", f64).unwrap() / 100.,
+                temperature: value_t!(matches, "ambient-temperature", f64).unwrap() + 273.15,
+            })
+        } else {
+            None
+        };
+        if matches.is_present("number-scans") && !matches.is_present("use-scanpos-names") {
+            panic!("--number-scans requires --use-scanpos-names");
+        }
+        let tilt_mount = tilt_mount(matches, &project);
+        let config = Config {
+            atmosphere: atmosphere,
+            bad_pixel_mode: bad_pixel_mode,
+            bad_pixels: bad_pixels,
+            bench: matches.is_present("bench"),
+            camera_calibration_files: matches
+                .values_of("camera-calibration")
+                .map(|values| values.map(PathBuf::from).collect())
+                .unwrap_or_default(),
+            cameras: cameras,
+            checksum_sidecars: matches.is_present("checksum-sidecars"),
+            checksums: matches.is_present("checksums") || matches.is_present("checksum-sidecars"),
+            coverage: matches.is_present("coverage"),
+            delta_dir: matches.value_of("delta-dir").map(PathBuf::from),
+            distortions: distortions,
+            dual_output: matches.is_present("dual-output"),
+            emissivity: value_t!(matches, "emissivity", f64).unwrap(),
+            emissivity_map: emissivity_map,
+            epochs: matches
+                .values_of("epoch")
+                .map(|values| {
+                    values
+                        .map(|value| {
+                            let mut iter = value.splitn(2, ':');
+                            let name = iter.next().unwrap();
+                            let image_dir = iter.next().expect(&format!(
+                                "--epoch must be NAME:DIR, got {}",
+                                value
+                            ));
+                            Epoch {
+                                name: name.to_string(),
+                                image_dir: PathBuf::from(image_dir),
+                            }
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            excluded_images: patterns(matches, "exclude-image"),
+            excluded_scan_positions: matches
+                .values_of("exclude-scan-position")
+                .map(|values| values.flat_map(scan_position_names).collect())
+                .unwrap_or_default(),
+            flag_without_thermal: if matches.is_present("flag-without-thermal") {
+                Some(value_t!(matches, "flag-without-thermal", Flag).unwrap())
+            } else {
+                None
+            },
+            flat_fields: flat_fields,
+            geojson: matches.value_of("geojson").map(PathBuf::from),
             image_dir: image_dir,
+            image_extensions: matches
+                .values_of("image-ext")
+                .unwrap()
+                .map(|extension| extension.trim_left_matches('.').to_lowercase())
+                .collect(),
+            image_ids: matches.is_present("image-ids"),
+            image_layout: image_layout,
+            image_map: matches.value_of("image-map").map(|path| {
+                read_image_map(path).expect(&format!("Could not read image map {}", path))
+            }),
+            image_stats: matches.is_present("image-stats"),
+            image_time_format: matches.value_of("image-time-format").map(|s| s.to_string()),
+            input_dir: matches.value_of("input-dir").map(PathBuf::from),
+            input_frame: value_t!(matches, "input-frame", Frame).unwrap(),
             keep_without_thermal: matches.is_present("keep-without-thermal"),
+            kml: matches.value_of("kml").map(PathBuf::from),
             las_dir: las_dir,
+            las_version: las_version,
+            laz: matches.is_present("laz"),
+            laz_chunk_size: matches.value_of("laz-chunk-size").map(|_| {
+                value_t!(matches, "laz-chunk-size", u32).unwrap()
+            }),
+            mask_dir: matches.value_of("mask-dir").map(PathBuf::from),
             max_reflectance: max_reflectance,
+            max_temperature: max_temperature,
+            max_temperature_spread: matches.value_of("max-temperature-spread").map(|_| {
+                value_t!(matches, "max-temperature-spread", f64).unwrap()
+            }),
+            max_time_offset: max_time_offset,
+            memory: MemoryBudget::new(matches.value_of("max-memory").map(|_| {
+                value_t!(matches, "max-memory", f64).unwrap()
+            })),
+            merge_ortho: matches.is_present("merge-ortho"),
+            mmap_images: matches.is_present("mmap-images"),
+            min_images: value_t!(matches, "min-images", usize).unwrap(),
             min_reflectance: min_reflectance,
+            min_temperature: min_temperature,
+            nir_temperature: nir_temperature,
+            nodata_value: nodata_value,
+            number_scans: matches.is_present("number-scans"),
+            offset: value_t!(matches, "offset", Offset).unwrap(),
+            only_images: patterns(matches, "only-images"),
+            orientation: orientation(matches),
+            ortho_resolution: matches.value_of("ortho").map(|_| {
+                value_t!(matches, "ortho", f64).unwrap()
+            }),
+            ortho_statistic: value_t!(matches, "ortho-statistic", Statistic).unwrap(),
+            output_layout: value_t!(matches, "output-layout", OutputLayout).unwrap(),
+            point_format: point_format,
+            pop_overridden: matches.is_present("pop"),
+            position_rotations: position_rotations,
+            preview_width: matches.value_of("preview").map(|_| {
+                value_t!(matches, "preview", usize).unwrap()
+            }),
             project: project,
-            rotate: matches.is_present("rotate"),
+            projection_luts: projection_luts,
+            reflected_temperature: value_t!(matches, "reflected-temperature", f64).unwrap() +
+                273.15,
+            rejections: matches.is_present("rejections"),
+            retry: Retry {
+                retries: value_t!(matches, "retries", u32).unwrap(),
+                delay: std::time::Duration::from_millis(
+                    (value_t!(matches, "retry-delay", f64).unwrap() * 1000.) as u64,
+                ),
+            },
+            rgb_dir: matches.value_of("rgb-dir").map(PathBuf::from),
+            scale: value_t!(matches, "scale", f64).unwrap(),
+            scan_position_aliases: scan_position_aliases,
             scan_position_names: matches.values_of("scan-position").map(|values| {
-                values.map(|name| name.to_string()).collect()
+                values.flat_map(scan_position_names).collect()
             }),
-            sync_to_pps: matches.is_present("sync-to-pps"),
+            scan_time_format: matches.value_of("scan-time-format").unwrap().to_string(),
+            scans: patterns(matches, "scan"),
+            skip_points: value_t!(matches, "skip-points", usize).unwrap(),
+            sop_file: matches.value_of("sop-file").map(PathBuf::from),
+            split_by_temperature: split_by_temperature,
+            sync_to_pps: if matches.is_present("sync-to-pps") {
+                Some(true)
+            } else if matches.is_present("no-sync-to-pps") {
+                Some(false)
+            } else {
+                None
+            },
             temperature_gradient: temperature_gradient,
+            thermal_orthos: matches
+                .values_of("thermal-ortho")
+                .map(|values| {
+                    values
+                        .map(|path| {
+                            let ortho = GeoTiff::from_path(path).expect(&format!(
+                                "Could not read thermal orthophoto {}",
+                                path
+                            ));
+                            (PathBuf::from(path), ortho)
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            tilt_images: patterns(matches, "tilt-images"),
+            tilt_mount: tilt_mount,
+            time_window: if matches.is_present("time-window") {
+                Some(value_t!(matches, "time-window", TimeWindow).unwrap())
+            } else {
+                None
+            },
+            timings: matches.is_present("timings"),
+            transform: matches.value_of("transform").map(|path| {
+                let mut s = String::new();
+                File::open(path).unwrap().read_to_string(&mut s).unwrap();
+                let transform = matrix::parse(&s).expect(&format!("Invalid transform in {}", path));
+                (PathBuf::from(path), transform)
+            }),
+            unlinked_images: Mutex::new(BTreeSet::new()),
+            unreadable_images: Mutex::new(BTreeSet::new()),
+            upload: matches.value_of("output-url").map(|_| {
+                value_t!(matches, "output-url", Upload).unwrap()
+            }),
             use_scanpos_names: matches.is_present("use-scanpos-names"),
+            valid_temperatures: (
+                matches
+                    .value_of("min-valid-temperature")
+                    .map(|_| value_t!(matches, "min-valid-temperature", f64).unwrap())
+                    .unwrap_or(std::f64::NEG_INFINITY),
+                matches
+                    .value_of("max-valid-temperature")
+                    .map(|_| value_t!(matches, "max-valid-temperature", f64).unwrap())
+                    .unwrap_or(std::f64::INFINITY),
+            ),
+            utm_zone: matches.value_of("utm-zone").map(|_| {
+                value_t!(matches, "utm-zone", UtmZone).unwrap()
+            }),
             name_map: name_map,
+        };
+        config.check_scanpos_names();
+        config.check_calibrations();
+        config.check_time_window();
+        config.check_laszip();
+        config
+    }
+
+    /// Panics if LAZ files will be read or written, but laszip can't be run.
+    fn check_laszip(&self) {
+        let reads_laz = self.input_dir.is_some() &&
+            self.scan_positions().into_iter().any(|scan_position| {
+                self.scan_paths(scan_position).iter().any(|infile| {
+                    infile
+                        .extension()
+                        .map(|extension| extension.to_string_lossy().to_lowercase() == "laz")
+                        .unwrap_or(false)
+                })
+            });
+        if self.laz || reads_laz {
+            laz::check().expect("LAZ files are converted with laszip, which must be on the PATH");
         }
     }
 
-    fn translations(&self, scan_position: &ScanPosition) -> Vec<Translation> {
-        let paths = scan_position.singlescan_rxp_paths(&self.project);
-        if self.use_scanpos_names && paths.len() > 1 {
-            panic!(
-                "--use-scanpos-names was provided, but there are {} rxp files for scan position {}",
-                paths.len(),
-                scan_position.name
-            );
+    /// Panics, listing the input files, if there's a `--time-window` and any selected input file
+    /// doesn't have point times, i.e. a PTX or rdbx file.
+    fn check_time_window(&self) {
+        if self.time_window.is_none() || self.input_dir.is_none() {
+            return;
         }
-        paths
+        let infiles = self.scan_positions()
             .into_iter()
-            .map(|path| {
-                Translation {
-                    outfile: self.outfile(scan_position, &path),
-                    infile: path,
-                }
+            .flat_map(|scan_position| self.scan_paths(scan_position))
+            .filter(|infile| {
+                let extension = infile
+                    .extension()
+                    .map(|extension| extension.to_string_lossy().to_lowercase())
+                    .unwrap_or_default();
+                extension == "ptx" || extension == "rdbx"
             })
-            .collect()
+            .map(|infile| infile.display().to_string())
+            .collect::<Vec<_>>();
+        if !infiles.is_empty() {
+            panic!(
+                "--time-window requires point times, but these files don't have them:\n  - {}",
+                infiles.join("\n  - ")
+            );
+        }
     }
 
-    fn colorize(&self, scan_position: &ScanPosition, translation: &Translation) {
-        use std::f64;
-
-        let image_groups = self.image_groups(scan_position);
-        let stream = Stream::from_path(&translation.infile)
-            .sync_to_pps(self.sync_to_pps)
-            .open()
-            .unwrap();
-        let mut writer = las::Writer::from_path(&translation.outfile, self.las_header()).unwrap();
+    /// Panics, listing every image and the calibration it's missing, if any image of a selected
+    /// scan position and camera refers to a camera or mount calibration that isn't in the project.
+    fn check_calibrations(&self) {
+        let mut problems = Vec::new();
+        for scan_position in self.scan_positions() {
+            for image in scan_position.images.values() {
+                if !self.is_selected_camera(image) {
+                    continue;
+                }
+                if !self.project
+                    .camera_calibrations
+                    .contains_key(&image.camera_calibration_name)
+                {
+                    problems.push(format!(
+                        "{} -> camera calibration {}",
+                        image.name,
+                        image.camera_calibration_name
+                    ));
+                }
+                if !self.project
+                    .mount_calibrations
+                    .contains_key(&image.mount_calibration_name)
+                {
+                    problems.push(format!(
+                        "{} -> mount calibration {}",
+                        image.name,
+                        image.mount_calibration_name
+                    ));
+                }
+            }
+        }
+        if !problems.is_empty() {
+            panic!(
+                "These images refer to calibrations that aren't in the project:\n  - {}",
+                problems.join("\n  - ")
+            );
+        }
+    }
 
-        for point in stream {
-            let point = point.expect("could not read rxp point");
-            let socs = Point::socs(point.x, point.y, point.z);
-            let temperatures = image_groups
-                .iter()
-                .filter_map(|image_group| image_group.temperature(&socs))
-                .collect::<Vec<_>>();
-            let temperature = if temperatures.is_empty() {
-                if self.keep_without_thermal {
-                    f64::NAN
+    /// Panics, listing the offending scan positions, if `--use-scanpos-names` would give more
+    /// than one scan the same output file.
+    fn check_scanpos_names(&self) {
+        if !self.use_scanpos_names || self.number_scans {
+            return;
+        }
+        let offenders = self.scan_positions()
+            .into_iter()
+            .filter_map(|scan_position| {
+                let count = self.scan_paths(scan_position).len();
+                if count > 1 {
+                    Some(format!("{} ({} scans)", scan_position.name, count))
                 } else {
-                    continue;
+                    None
                 }
-            } else {
-                temperatures.iter().sum::<f64>() / temperatures.len() as f64
-            };
-            let glcs = socs.to_prcs(scan_position.sop).to_glcs(self.project.pop);
-            let point = las::Point {
-                x: glcs.x,
-                y: glcs.y,
-                z: glcs.z,
-                intensity: self.to_intensity(point.reflectance),
-                color: Some(self.to_color(temperature as f32)),
-                gps_time: Some(temperature),
-                ..Default::default()
-            };
-            writer.write(point).expect("could not write las point");
+            })
+            .collect::<Vec<_>>();
+        if !offenders.is_empty() {
+            panic!(
+                "--use-scanpos-names was provided, but these scan positions have more than one \
+                 scan (use --number-scans to number them): {}",
+                offenders.join(", ")
+            );
         }
     }
 
-    fn scan_positions(&self) -> Vec<&ScanPosition> {
-        let mut scan_positions: Vec<_> = if let Some(names) = self.scan_position_names.as_ref() {
-            names
-                .iter()
-                .map(|name| self.project.scan_positions.get(name).unwrap())
-                .collect()
+    /// Returns the selected input files of a scan position, sorted by path.
+    fn scan_paths(&self, scan_position: &ScanPosition) -> Vec<PathBuf> {
+        let paths = if let Some(input_dir) = self.input_dir.as_ref() {
+            self.input_paths(scan_position, input_dir)
         } else {
-            self.project.scan_positions.values().collect()
+            scan_position.singlescan_rxp_paths(&self.project)
         };
-        scan_positions.sort_by_key(|s| &s.name);
-        scan_positions
+        let mut paths = paths
+            .into_iter()
+            .filter(|path| self.is_selected_scan(scan_position, path))
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
     }
 
-    fn to_color(&self, n: f32) -> Color {
-        let color = self.temperature_gradient.get(n);
-        Color {
-            red: (u16::MAX as f32 * color.red) as u16,
-            green: (u16::MAX as f32 * color.green) as u16,
-            blue: (u16::MAX as f32 * color.blue) as u16,
-        }
+    fn translations(&self, scan_position: &ScanPosition) -> Vec<Translation> {
+        let paths = self.scan_paths(scan_position);
+        let is_numbered = self.number_scans && paths.len() > 1;
+        let mut translations = Vec::new();
+        for (i, path) in paths.into_iter().enumerate() {
+            let number = if is_numbered { Some(i + 1) } else { None };
+            let outfile = self.outfile(scan_position, &path, number);
+            for (epoch, image_dir) in self.image_dirs() {
+                let outfile = match epoch {
+                    Some(epoch) => with_suffix(&outfile, epoch),
+                    None => outfile.clone(),
+                };
+                translations.push(Translation {
+                    epoch: epoch.map(|epoch| epoch.to_string()),
+                    image_dir: image_dir.to_path_buf(),
+                    rgb_outfile: if self.dual_output {
+                        Some(with_suffix(&outfile, "rgb"))
+                    } else {
+                        None
+                    },
+                    split_outfiles: self.split_names()
+                        .iter()
+                        .map(|name| with_suffix(&outfile, name))
+                        .collect(),
+                    outfile: outfile,
+                    infile: path.clone(),
+                });
+            }
+        }
+        translations
+    }
+
+    /// Returns the suffixes of the `--split-by-temperature` outputs, coldest first.
+    ///
+    /// One or two breaks give "cold" and "hot" ranges, with "normal" in between; more breaks give
+    /// numbered ranges.
+    fn split_names(&self) -> Vec<String> {
+        match self.split_by_temperature.len() {
+            0 => Vec::new(),
+            1 => vec!["cold".to_string(), "hot".to_string()],
+            2 => vec!["cold".to_string(), "normal".to_string(), "hot".to_string()],
+            n => (1..n + 2).map(|i| format!("range{}", i)).collect(),
+        }
+    }
+
+    /// Returns the number of las files written for each translation.
+    fn writers_per_translation(&self) -> usize {
+        1 + if self.dual_output { 1 } else { 0 } + self.split_names().len()
+    }
+
+    /// Returns the index of the `--split-by-temperature` range that a temperature falls in, or
+    /// `None` if the temperature is NaN.
+    fn split_index(&self, temperature: f64) -> Option<usize> {
+        if temperature.is_nan() {
+            None
+        } else {
+            Some(
+                self.split_by_temperature
+                    .iter()
+                    .filter(|&&temperature_break| temperature >= temperature_break)
+                    .count(),
+            )
+        }
+    }
+
+    /// Returns the input file for this scan position, `<input dir>/<scan position>.<las, laz, ptx,
+    /// or rdbx>`.
+    fn input_paths(&self, scan_position: &ScanPosition, input_dir: &Path) -> Vec<PathBuf> {
+        ["las", "LAS", "laz", "LAZ", "ptx", "PTX", "rdbx"]
+            .iter()
+            .map(|extension| input_dir.join(format!("{}.{}", scan_position.name, extension)))
+            .find(|path| path.is_file())
+            .into_iter()
+            .collect()
+    }
+
+    /// Reads the points of an input file, converting them to the scanner's own coordinate system.
+    ///
+    /// Rxp files are read with scanifc, rdbx files with rdblib (if tce was built with the `rdb`
+    /// feature), ptx files with our own reader, laz files as las after they're decompressed with
+    /// laszip, and every other file is read as las. Rxp points are always in the scanner's own
+    /// coordinate system, and the others are in the `--input-frame`.
+    ///
+    /// Points that can't be read, e.g. because the file is corrupt or truncated, are errors.
+    fn points<'a>(
+        &'a self,
+        scan_position: &'a ScanPosition,
+        infile: &Path,
+    ) -> Box<Iterator<Item = Result<ScanPoint, String>> + 'a> {
+        let extension = infile
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let frame = self.input_frame;
+        let (sop, pop) = (scan_position.sop, self.project.pop);
+        let to_socs = move |x, y, z| match frame {
+            Frame::Socs => Point::socs(x, y, z),
+            Frame::Prcs => Point::prcs(x, y, z).to_socs(sop),
+            Frame::Glcs => Point::glcs(x, y, z).to_prcs(pop).to_socs(sop),
+        };
+        if extension == "ptx" {
+            let reader = self.retry
+                .run(&format!("open {}", infile.display()), || {
+                    ptx::Reader::from_path(infile)
+                })
+                .unwrap();
+            return Box::new(reader.map(move |point| {
+                point
+                    .map(|point| {
+                        ScanPoint {
+                            socs: to_socs(point.x, point.y, point.z),
+                            intensity: (u16::MAX as f64 * point.intensity.max(0.).min(1.)) as u16,
+                            time: None,
+                        }
+                    })
+                    .map_err(|err| format!("could not read ptx point: {:?}", err))
+            }));
+        }
+        if extension == "rdbx" {
+            return self.rdbx_points(infile, to_socs);
+        }
+        if extension == "rxp" {
+            return self.rxp_points(infile);
+        }
+        let temporary_file = if extension == "laz" {
+            Some(
+                self.retry
+                    .run(&format!("decompress {}", infile.display()), || {
+                        TemporaryFile::laszip(infile)
+                    })
+                    .unwrap(),
+            )
+        } else {
+            None
+        };
+        let path = temporary_file
+            .as_ref()
+            .map(|temporary_file| temporary_file.0.clone())
+            .unwrap_or_else(|| infile.to_path_buf());
+        let mut reader = self.retry
+            .run(&format!("open {}", infile.display()), || {
+                las::Reader::from_path(&path)
+            })
+            .unwrap();
+        let points = std::iter::repeat(())
+            .map(move |_| reader.read())
+            .take_while(|point| match *point {
+                Ok(None) => false,
+                _ => true,
+            })
+            .map(|point| point.map(|point| point.unwrap()));
+        Box::new(points.map(move |point| {
+            // Keeps the decompressed laz file until its points are read.
+            let _ = &temporary_file;
+            point
+                .map(|point| {
+                    ScanPoint {
+                        socs: to_socs(point.x, point.y, point.z),
+                        intensity: point.intensity,
+                        time: point.gps_time,
+                    }
+                })
+                .map_err(|err| format!("could not read las point: {:?}", err))
+        }))
+    }
+
+    /// Reads the points of an input file, without the `--skip-points` and outside the
+    /// `--time-window`, which are counted in `filtered`.
+    fn selected_points<'a>(
+        &'a self,
+        scan_position: &'a ScanPosition,
+        infile: &Path,
+        filtered: &'a Cell<u64>,
+    ) -> Box<Iterator<Item = Result<ScanPoint, String>> + 'a> {
+        let mut points = self.points(scan_position, infile);
+        if self.skip_points > 0 {
+            let skip_points = self.skip_points;
+            points = Box::new(points.enumerate().filter_map(move |(i, point)| {
+                if i < skip_points {
+                    filtered.set(filtered.get() + 1);
+                    None
+                } else {
+                    Some(point)
+                }
+            }));
+        }
+        if let Some(window) = self.time_window {
+            let infile = infile.to_path_buf();
+            points = Box::new(points.filter(move |point| {
+                let point = match *point {
+                    Ok(ref point) => point,
+                    Err(_) => return true,
+                };
+                let time = point.time.expect(&format!(
+                    "--time-window requires point times, but {} doesn't have them",
+                    infile.display()
+                ));
+                let is_selected = time >= window.start && time <= window.end;
+                if !is_selected {
+                    filtered.set(filtered.get() + 1);
+                }
+                is_selected
+            }));
+        }
+        points
+    }
+
+    /// Returns the number of points in each chunk passed between the stages of the pipeline.
+    ///
+    /// Each chunk is read as scan points and written as las points, and up to `PIPELINE_DEPTH`
+    /// chunks wait between stages, all of which must fit in the `--max-memory` chunk budget.
+    fn chunk_len(&self) -> usize {
+        use std::mem::size_of;
+
+        let size = size_of::<ScanPoint>() + size_of::<OutputPoint>();
+        self.memory.chunk_len((PIPELINE_DEPTH + 2) * size, CHUNK_LEN)
+    }
+
+    /// Reads the points of an rxp file, deciding whether to only read pps-synced points.
+    ///
+    /// Unless the user decided for us, we sync to pps if any of the first `PPS_PROBE_POINTS`
+    /// points are synced. The probe reads the file without syncing, so if there aren't any synced
+    /// points, its points are read again from memory and the file is only opened once. If the
+    /// user asked for pps-synced points and there aren't any, that's an error, since the output
+    /// would be empty.
+    fn rxp_points<'a>(
+        &'a self,
+        infile: &Path,
+    ) -> Box<Iterator<Item = Result<ScanPoint, String>> + 'a> {
+        // pps sync is the only setting of scanifc's `Stream`, which wraps
+        // `scanifc_point3dstream_open(uri, sync_to_pps, h3ds)` in RiVLib's scanifc.h, so there are
+        // no multiple time around (MTA) settings to pass along.
+        let open = |sync_to_pps| {
+            self.retry
+                .run(&format!("open {}", infile.display()), || {
+                    Stream::from_path(infile).sync_to_pps(sync_to_pps).open()
+                })
+                .unwrap()
+        };
+        let to_scan_point = move |point: Result<scanifc::point3d::Point, scanifc::Error>| {
+            point
+                .map(|point| {
+                    ScanPoint {
+                        socs: Point::socs(point.x, point.y, point.z),
+                        intensity: self.to_intensity(point.reflectance),
+                        time: Some(point.time),
+                    }
+                })
+                .map_err(|err| format!("could not read rxp point: {:?}", err))
+        };
+        match self.sync_to_pps {
+            Some(false) => Box::new(open(false).map(to_scan_point)),
+            Some(true) => {
+                // If there are no synced points, scanifc reads the whole file before telling us so,
+                // but then there's nothing to colorize anyway.
+                let mut stream = open(true).peekable();
+                if stream.peek().is_none() {
+                    return Box::new(std::iter::once(Err(format!(
+                        "--sync-to-pps was provided, but {} has no pps-synced points",
+                        infile.display()
+                    ))));
+                }
+                Box::new(stream.map(to_scan_point))
+            }
+            None => {
+                let mut stream = open(false);
+                let probe = stream.by_ref().take(PPS_PROBE_POINTS).collect::<Vec<_>>();
+                let has_synced_points = probe.iter().any(|point| {
+                    point.as_ref().map(|point| point.is_pps_locked).unwrap_or(false)
+                });
+                info!(
+                    "    - {} pps-synced points, sync to pps: {}",
+                    if has_synced_points { "Found" } else { "No" },
+                    has_synced_points
+                );
+                if has_synced_points {
+                    Box::new(open(true).map(to_scan_point))
+                } else {
+                    Box::new(probe.into_iter().chain(stream).map(to_scan_point))
+                }
+            }
+        }
+    }
+
+    /// Reads the points of an rdbx file, converting them with `to_socs`.
+    ///
+    /// A file that can't be opened is an error, like a point that can't be read.
+    #[cfg(feature = "rdb")]
+    fn rdbx_points<'a, F>(
+        &'a self,
+        infile: &Path,
+        to_socs: F,
+    ) -> Box<Iterator<Item = Result<ScanPoint, String>> + 'a>
+    where
+        F: Fn(f64, f64, f64) -> Point<Socs> + 'a,
+    {
+        let reader = match self.retry.run(&format!("open {}", infile.display()), || {
+            rdb::Reader::from_path(infile)
+        }) {
+            Ok(reader) => reader,
+            Err(err) => {
+                return Box::new(std::iter::once(
+                    Err(format!("could not open {}: {}", infile.display(), err)),
+                ));
+            }
+        };
+        Box::new(reader.map(move |point| {
+            point
+                .map(|point| {
+                    ScanPoint {
+                        socs: to_socs(point.x, point.y, point.z),
+                        intensity: self.to_intensity(point.reflectance),
+                        time: None,
+                    }
+                })
+                .map_err(|err| format!("could not read rdbx point: {:?}", err))
+        }))
+    }
+
+    #[cfg(not(feature = "rdb"))]
+    fn rdbx_points<'a, F>(
+        &'a self,
+        infile: &Path,
+        _: F,
+    ) -> Box<Iterator<Item = Result<ScanPoint, String>> + 'a>
+    where
+        F: Fn(f64, f64, f64) -> Point<Socs> + 'a,
+    {
+        panic!(
+            "Can't read {}: tce was built without rdbx support, rebuild with `--features rdb`",
+            infile.display()
+        );
+    }
+
+    /// Returns a progress bar over the estimated number of points in an input file.
+    ///
+    /// The progress bar is hidden if informational messages aren't printed, e.g. with `--quiet`,
+    /// or if progress is reported with JSON events.
+    fn progress_bar(&self, infile: &Path) -> ProgressBar {
+        if !logger::prints(Level::Info) || logger::emits_events() {
+            return ProgressBar::hidden();
+        }
+        let progress = ProgressBar::new(self.estimated_point_count(infile));
+        progress.set_style(ProgressStyle::default_bar().template(
+            "    {bar:40} {percent}% {pos} read, {msg} ({per_sec}, ETA {eta})",
+        ));
+        progress
+    }
+
+    /// Returns the number of points in an input file.
+    ///
+    /// Only las files store a point count that we can read without decompressing them, for
+    /// everything else it's estimated from the file size.
+    fn estimated_point_count(&self, infile: &Path) -> u64 {
+        let extension = infile
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        let bytes_per_point = match extension.as_str() {
+            "las" => {
+                return las::Reader::from_path(infile)
+                    .map(|reader| reader.header().number_of_points())
+                    .unwrap_or(0);
+            }
+            "laz" => LAZ_BYTES_PER_POINT,
+            "ptx" => PTX_BYTES_PER_POINT,
+            "rdbx" => RDBX_BYTES_PER_POINT,
+            _ => RXP_BYTES_PER_POINT,
+        };
+        fs::metadata(infile)
+            .map(|metadata| metadata.len() / bytes_per_point)
+            .unwrap_or(0)
+    }
+
+    /// Returns true if there's no `--scan` or if one matches the input file.
+    ///
+    /// The patterns are matched against the file name, the file stem, and the name of the
+    /// project's scan with that file.
+    fn is_selected_scan(&self, scan_position: &ScanPosition, path: &Path) -> bool {
+        if self.scans.is_empty() {
+            return true;
+        }
+        let file_name = path.file_name()
+            .map(|file_name| file_name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut names = vec![
+            path.file_stem()
+                .map(|file_stem| file_stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        ];
+        names.extend(
+            scan_position
+                .scans
+                .values()
+                .filter(|scan| scan.file == file_name)
+                .map(|scan| scan.name.clone()),
+        );
+        names.push(file_name);
+        self.scans
+            .iter()
+            .any(|pattern| names.iter().any(|name| pattern.matches(name)))
+    }
+
+    /// Returns the path, size, and modification time of each of a scan position's images, which
+    /// change when images are added, removed, or rewritten.
+    fn imagery_fingerprint(
+        &self,
+        scan_position: &ScanPosition,
+    ) -> Vec<(PathBuf, u64, Option<std::time::SystemTime>)> {
+        file_fingerprint(
+            self.image_dirs()
+                .into_iter()
+                .flat_map(|(_, image_dir)| self.image_paths(scan_position, image_dir)),
+        )
+    }
+
+    /// Returns a hash of the fingerprint of a translation's input file and its thermal and
+    /// baseline images.
+    fn input_fingerprint(&self, scan_position: &ScanPosition, translation: &Translation) -> String {
+        use std::time::UNIX_EPOCH;
+
+        let mut paths = vec![translation.infile.clone()];
+        paths.extend(self.image_paths(scan_position, &translation.image_dir));
+        if let Some(delta_dir) = self.delta_dir.as_ref() {
+            paths.extend(self.image_paths(scan_position, delta_dir));
+        }
+        let mut s = String::new();
+        for (path, len, modified) in file_fingerprint(paths) {
+            let modified = modified
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| (duration.as_secs(), duration.subsec_nanos()))
+                .unwrap_or((0, 0));
+            s.push_str(&format!(
+                "{}\t{}\t{}.{:09}\n",
+                path.display(),
+                len,
+                modified.0,
+                modified.1
+            ));
+        }
+        sha256::string(&s)
+    }
+
+    /// Returns the image directory of each epoch, or just the image directory if there are no
+    /// epochs.
+    fn image_dirs(&self) -> Vec<(Option<&str>, &Path)> {
+        if self.epochs.is_empty() {
+            vec![(None, self.image_dir.as_path())]
+        } else {
+            self.epochs
+                .iter()
+                .map(|epoch| (Some(epoch.name.as_str()), epoch.image_dir.as_path()))
+                .collect()
+        }
+    }
+
+    /// Returns an empty histogram over the color scale's domain.
+    fn histogram(&self) -> Histogram {
+        Histogram::new(f64::from(self.min_temperature), f64::from(self.max_temperature))
+    }
+
+    fn scan_positions(&self) -> Vec<&ScanPosition> {
+        let mut scan_positions: Vec<_> = if let Some(names) = self.scan_position_names.as_ref() {
+            names
+                .iter()
+                .flat_map(|name| self.matching_scan_positions(name))
+                .collect()
+        } else {
+            self.project.scan_positions.values().collect()
+        };
+        for selector in &self.excluded_scan_positions {
+            let excluded = self.matching_scan_positions(selector);
+            scan_positions.retain(|scan_position| {
+                !excluded.iter().any(|excluded| excluded.name == scan_position.name)
+            });
+        }
+        scan_positions.sort_by_key(|s| &s.name);
+        scan_positions.dedup_by_key(|s| &s.name);
+        scan_positions
+    }
+
+    /// Returns the scan positions selected by one `--scan-position` value.
+    ///
+    /// The value is a scan position name, a glob (e.g. `SP0*`), or a regex between slashes (e.g.
+    /// `/^SP0[1-5]$/`). Panics if nothing matches.
+    fn matching_scan_positions(&self, selector: &str) -> Vec<&ScanPosition> {
+        if let Some(scan_position) = self.project.scan_positions.get(selector) {
+            return vec![scan_position];
+        }
+        let is_match: Box<Fn(&str) -> bool> =
+            if selector.len() > 1 && selector.starts_with('/') && selector.ends_with('/') {
+                let regex = Regex::new(&selector[1..selector.len() - 1])
+                    .expect(&format!("Invalid scan position regex: {}", selector));
+                Box::new(move |name| regex.is_match(name))
+            } else {
+                let pattern = Pattern::new(selector)
+                    .expect(&format!("Invalid scan position pattern: {}", selector));
+                Box::new(move |name| pattern.matches(name))
+            };
+        let scan_positions = self.project
+            .scan_positions
+            .values()
+            .filter(|scan_position| is_match(&scan_position.name))
+            .collect::<Vec<_>>();
+        if scan_positions.is_empty() {
+            panic!("No scan positions match {}", selector);
+        }
+        scan_positions
+    }
+
+    fn to_color(&self, n: f32) -> Color {
+        let color = self.temperature_gradient.get(n);
+        Color {
+            red: (u16::MAX as f32 * color.red) as u16,
+            green: (u16::MAX as f32 * color.green) as u16,
+            blue: (u16::MAX as f32 * color.blue) as u16,
+        }
     }
 
     fn to_intensity(&self, n: f32) -> u16 {
@@ -238,133 +2014,1819 @@ impl Config {
              (self.max_reflectance - self.min_reflectance)) as u16
     }
 
-    fn las_header(&self) -> las::Header {
+    /// Scales a temperature into the `--nir-temperature` domain, or returns zero if temperatures
+    /// aren't stored in NIR.
+    fn to_nir(&self, temperature: f64) -> u16 {
+        match self.nir_temperature {
+            Some((min, max)) if !temperature.is_nan() => {
+                let scaled = (temperature as f32 - min) / (max - min);
+                (u16::MAX as f32 * scaled.max(0.).min(1.)) as u16
+            }
+            _ => 0,
+        }
+    }
+
+    /// Applies the `--transform`, if any, to a point in the project's global coordinate system.
+    fn to_output(&self, glcs: &Point<Glcs>) -> Point3<f64> {
+        match self.transform {
+            Some((_, transform)) => transform * **glcs,
+            None => **glcs,
+        }
+    }
+
+    fn las_header(
+        &self,
+        offset: Point3<f64>,
+        scan_position: &ScanPosition,
+        translation: &Translation,
+        image_groups: &[ImageGroup],
+    ) -> las::Header {
         let mut header = las::Header::default();
-        header.point_format = Format::new(3).unwrap();
+        header.version = las::Version::new(self.las_version.0, self.las_version.1);
+        header.point_format = Format::new(self.point_format).unwrap();
         header.transforms = las::Vector {
             x: las::Transform {
-                scale: 0.001,
-                offset: self.project.pop[(0, 3)],
+                scale: self.scale,
+                offset: offset.x,
             },
             y: las::Transform {
-                scale: 0.001,
-                offset: self.project.pop[(1, 3)],
+                scale: self.scale,
+                offset: offset.y,
             },
             z: las::Transform {
-                scale: 0.001,
-                offset: self.project.pop[(2, 3)],
+                scale: self.scale,
+                offset: offset.z,
             },
         };
+        self.extra_attributes().apply(&mut header);
+        header.vlrs.push(self.provenance_vlr(scan_position, translation, image_groups));
         header
     }
 
-    fn image_groups<'a>(&'a self, scan_position: &'a ScanPosition) -> Vec<ImageGroup<'a>> {
-        let mut image_dir = self.image_dir.clone();
-        image_dir.push(&scan_position.name);
-        match fs::read_dir(image_dir) {
-            Ok(read_dir) => {
-                read_dir
-                    .enumerate()
-                    .filter_map(|(i, entry)| {
-                        let entry = entry.unwrap();
-                        if entry.path().extension().map(|e| e == "irb").unwrap_or(
-                            false,
-                        )
-                        {
-                            let image = if let Some(name) = self.name_map(scan_position) {
-                                let image_name = format!("{} - Image{:03}", name, i + 1);
-                                scan_position.images.get(&image_name).expect(&format!(
-                                    "Could not find image {}",
-                                    image_name
-                                ))
-                            } else {
-                                scan_position.image_from_path(entry.path()).unwrap()
-                            };
-                            let irb = Irb::from_path(entry.path().to_string_lossy().as_ref())
-                                .unwrap();
-                            let camera_calibration =
-                                image.camera_calibration(&self.project).unwrap();
-                            let mount_calibration = image.mount_calibration(&self.project).unwrap();
-                            Some(ImageGroup {
-                                camera_calibration: camera_calibration,
-                                image: image,
-                                irb: irb,
-                                irb_path: entry.path(),
-                                mount_calibration: mount_calibration,
-                                rotate: self.rotate,
-                            })
-                        } else {
-                            None
-                        }
+    /// Returns a vlr with a JSON description of how the output file was made.
+    ///
+    /// With `--image-ids`, this includes the image file of each id.
+    fn provenance_vlr(
+        &self,
+        scan_position: &ScanPosition,
+        translation: &Translation,
+        image_groups: &[ImageGroup],
+    ) -> las::Vlr {
+        let sop = scan_position.sop.matrix();
+        let mut provenance = json!({
+            "tce_version": env!("CARGO_PKG_VERSION"),
+            "arguments": std::env::args().collect::<Vec<_>>(),
+            "project": self.project.path.display().to_string(),
+            "scan_position": scan_position.name,
+            "infile": translation.infile.display().to_string(),
+            "image_dir": translation.image_dir.display().to_string(),
+            "epoch": translation.epoch,
+            "sop": (0..4)
+                .map(|row| (0..4).map(|col| sop[(row, col)]).collect::<Vec<_>>())
+                .collect::<Vec<_>>(),
+        });
+        if self.image_ids {
+            provenance["image_ids"] = image_groups
+                .iter()
+                .enumerate()
+                .map(|(i, image_group)| {
+                    ((i + 1).to_string(), json!(image_group.irb_path.display().to_string()))
+                })
+                .collect::<serde_json::Map<_, _>>()
+                .into();
+        }
+        las::Vlr {
+            user_id: "tce".to_string(),
+            record_id: 1,
+            description: "tce provenance (JSON)".to_string(),
+            data: serde_json::to_vec(&provenance).unwrap(),
+        }
+    }
+
+    /// Returns the minimum of the points' output coordinates, rounded down to the nearest meter.
+    ///
+    /// Falls back to the project's origin if there aren't any points.
+    fn auto_offset(&self, scan_position: &ScanPosition, points: &[ScanPoint]) -> Point3<f64> {
+        use std::f64;
+
+        if points.is_empty() {
+            return self.to_output(&Point::prcs(0., 0., 0.).to_glcs(self.project.pop));
+        }
+        let mut offset = Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        for point in points {
+            let glcs = point.socs.to_prcs(scan_position.sop).to_glcs(self.project.pop);
+            let output = self.to_output(&glcs);
+            offset.x = offset.x.min(output.x);
+            offset.y = offset.y.min(output.y);
+            offset.z = offset.z.min(output.z);
+        }
+        Point3::new(offset.x.floor(), offset.y.floor(), offset.z.floor())
+    }
+
+    /// Creates a las writer, retrying if the file can't be created.
+    ///
+    /// Each translation's writers share the `--max-memory` buffer budget.
+    fn las_writer(
+        &self,
+        path: &Path,
+        header: &las::Header,
+    ) -> las::Writer<std::io::BufWriter<std::fs::File>> {
+        use std::fs::File;
+        use std::io::BufWriter;
+
+        let path = if self.laz {
+            uncompressed(path)
+        } else {
+            path.to_path_buf()
+        };
+        let capacity = self.memory
+            .buffer_len(self.writers_per_translation(), WRITE_BUFFER_BYTES);
+        self.retry
+            .run(&format!("create {}", path.display()), || {
+                File::create(&path)
+                    .map_err(las::Error::from)
+                    .and_then(|file| {
+                        las::Writer::new(BufWriter::with_capacity(capacity, file), header.clone())
                     })
-                    .collect()
+            })
+            .unwrap()
+    }
+
+    /// Compresses an output file's las, which was written next to it, with `--laz`.
+    fn compress(&self, outfile: &Path) {
+        let las = uncompressed(outfile);
+        self.retry
+            .run(&format!("compress {}", las.display()), || {
+                laz::compress(&las, outfile, self.laz_chunk_size)
+            })
+            .expect(&format!("Could not compress {}", las.display()));
+        fs::remove_file(&las).expect(&format!("Could not remove {}", las.display()));
+    }
+
+    /// Returns the SHA-256 of an output file, and writes its sidecar, if checksums are enabled.
+    ///
+    /// Returns `None` if checksums aren't enabled or if the file wasn't written, e.g. an empty
+    /// orthomosaic.
+    fn checksum(&self, path: &Path) -> Option<Checksum> {
+        if !self.checksums || !path.exists() {
+            return None;
+        }
+        let sha256 = sha256::file(path).expect(&format!("Could not hash {}", path.display()));
+        if self.checksum_sidecars {
+            let sidecar = sha256::write_sidecar(path, &sha256).expect(&format!(
+                "Could not write the checksum of {}",
+                path.display()
+            ));
+            self.publish(&sidecar);
+        }
+        Some(Checksum {
+            path: self.location(path),
+            sha256: sha256,
+        })
+    }
+
+    /// Uploads an output file to the `--output-url`, if there is one, and removes the local copy.
+    ///
+    /// Files in the las directory keep their relative path under the url's prefix, and every other
+    /// file, e.g. the `--summary`, goes directly under the prefix. Files that weren't written, e.g.
+    /// an empty orthomosaic, are ignored.
+    fn publish(&self, path: &Path) {
+        let upload = match self.upload.as_ref() {
+            Some(upload) => upload,
+            None => return,
+        };
+        if !path.is_file() {
+            return;
+        }
+        let url = self.retry
+            .run(&format!("upload {}", path.display()), || {
+                upload.put(path, &self.upload_key(path))
+            })
+            .expect(&format!("Could not upload {}", path.display()));
+        debug!("    - Uploaded {} to {}", path.display(), url);
+        fs::remove_file(path).expect(&format!("Could not remove {}", path.display()));
+    }
+
+    /// Returns where an output file ends up, its `--output-url` url or its local path.
+    fn location(&self, path: &Path) -> String {
+        match self.upload.as_ref() {
+            Some(upload) => upload.url(&self.upload_key(path)),
+            None => path.display().to_string(),
+        }
+    }
+
+    /// Returns the path of an output file under the `--output-url` prefix.
+    fn upload_key(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.las_dir)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| PathBuf::from(path.file_name().unwrap_or_default()))
+    }
+
+    /// Returns the attributes that are stored in each point's extra bytes.
+    fn extra_attributes(&self) -> Registry {
+        let mut registry = Registry::new();
+        if self.delta_dir.is_some() {
+            let attribute = Attribute::new("delta temperature", "Temperature minus baseline")
+                .units("°C");
+            registry.add(match self.nodata_value {
+                NodataValue::Value(value) => attribute.no_data(value),
+                NodataValue::Nan | NodataValue::OmitField => attribute,
+            });
+        }
+        if self.image_ids {
+            registry.add(
+                Attribute::new("image id", "Image nearest the point's pixel")
+                    .data_type(DataType::U32)
+                    .no_data(0.),
+            );
+        }
+        registry
+    }
+
+    fn image_groups<'a>(
+        &'a self,
+        scan_position: &'a ScanPosition,
+        image_dir: &Path,
+    ) -> Vec<ImageGroup<'a>> {
+        if let Some(image_map) = self.image_map.as_ref() {
+            return image_map
+                .iter()
+                .filter(|entry| {
+                    entry.scan_position == scan_position.name ||
+                        entry.scan_position == self.scan_position_dir_name(scan_position)
+                })
+                .filter_map(|entry| {
+                    let image = scan_position.images.get(&entry.image).expect(&format!(
+                        "Could not find image {} in scan position {}",
+                        entry.image,
+                        scan_position.name
+                    ));
+                    let path = image_dir.join(&entry.path);
+                    self.image_group(scan_position, image, path)
+                })
+                .collect();
+        }
+        self.image_paths(scan_position, image_dir)
+            .into_iter()
+            .filter(|path| self.image_extension(path).is_some())
+            .enumerate()
+            .filter_map(|(i, path)| {
+                self.image(scan_position, i, &path).and_then(|image| {
+                    self.image_group(scan_position, image, path)
+                })
+            })
+            .collect()
+    }
+
+    /// Decodes images into memory, in order, while they fit in the `--max-memory` image budget.
+    ///
+    /// Images that don't fit are sampled through their irb handle. With `--mmap-images`, every
+    /// image is decoded into a memory-mapped file instead, which doesn't count against the budget.
+    fn preload<'a, 'b: 'a, I>(&self, image_groups: I)
+    where
+        I: Iterator<Item = &'a mut ImageGroup<'b>>,
+    {
+        let mut remaining = self.memory.image_bytes();
+        for image_group in image_groups {
+            if self.mmap_images {
+                image_group.preload_mapped().expect(&format!(
+                    "Could not decode {} into a memory-mapped file",
+                    image_group.irb_path.display()
+                ));
+                continue;
             }
-            Err(err) => {
-                use std::io::ErrorKind;
-                match err.kind() {
-                    ErrorKind::NotFound => Vec::new(),
-                    _ => panic!("io error: {}", err),
-                }
+            let (width, height) = image_group.file_dimensions();
+            let bytes = (width * height * std::mem::size_of::<f32>()) as u64;
+            if remaining.map(|remaining| bytes > remaining).unwrap_or(false) {
+                debug!(
+                    "    - {} doesn't fit in --max-memory, not decoding it into memory",
+                    image_group.irb_path.display()
+                );
+                continue;
             }
+            image_group.preload();
+            remaining = remaining.map(|remaining| remaining - bytes);
         }
     }
 
-    fn outfile<P: AsRef<Path>>(&self, scan_position: &ScanPosition, infile: P) -> PathBuf {
-        let mut outfile = self.las_dir.clone();
-        if self.use_scanpos_names {
-            outfile.push(Path::new(&scan_position.name).with_extension("las"));
+    /// Returns the RGB photographs for this scan position, found in `<rgb dir>/<scan position>`.
+    ///
+    /// Photographs are matched to the project's images by file stem, and files that don't match an
+    /// image are ignored.
+    fn rgb_image_groups<'a>(&'a self, scan_position: &'a ScanPosition) -> Vec<RgbImageGroup<'a>> {
+        let mut dir = match self.rgb_dir.as_ref() {
+            Some(rgb_dir) => rgb_dir.clone(),
+            None => return Vec::new(),
+        };
+        dir.push(self.scan_position_dir_name(scan_position));
+        if !dir.is_dir() {
+            return Vec::new();
+        }
+        let mut paths = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+            .into_iter()
+            .filter_map(|path| {
+                let image = path.file_stem().and_then(|file_stem| {
+                    scan_position.images.get(file_stem.to_string_lossy().as_ref())
+                })?;
+                if !self.is_selected_camera(image) {
+                    return None;
+                }
+                let rgb = image::open(&path)
+                    .expect(&format!("Could not read image {}", path.display()))
+                    .to_rgb();
+                Some(RgbImageGroup {
+                    camera_calibration: image
+                        .camera_calibration(&self.project)
+                        .expect("check_calibrations found every selected camera calibration"),
+                    image: image,
+                    mount_calibration: self.mount_calibration(&path, image),
+                    path: path,
+                    rgb: rgb,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the name of this scan position's image directories, which is its alias if it has
+    /// one.
+    fn scan_position_dir_name<'a>(&'a self, scan_position: &'a ScanPosition) -> &'a str {
+        self.scan_position_aliases
+            .get(&scan_position.name)
+            .unwrap_or(&scan_position.name)
+    }
+
+    /// Returns the paths of all files that match the image layout for this scan position.
+    fn image_paths(&self, scan_position: &ScanPosition, image_dir: &Path) -> Vec<PathBuf> {
+        let pattern = self.image_layout
+            .replace(
+                "{root}",
+                &Pattern::escape(&image_dir.to_string_lossy()),
+            )
+            .replace(
+                "{scanpos}",
+                &Pattern::escape(self.scan_position_dir_name(scan_position)),
+            );
+        let mut paths = glob::glob(&pattern)
+            .expect(&format!("Invalid image layout: {}", pattern))
+            .map(|path| path.unwrap())
+            .filter(|path| path.is_file())
+            .collect::<Vec<_>>();
+        paths.sort();
+        paths
+    }
+
+    /// Finds the project image for the i-th image file of a scan position.
+    fn image<'a>(
+        &self,
+        scan_position: &'a ScanPosition,
+        i: usize,
+        path: &Path,
+    ) -> Option<&'a Image> {
+        if let Some(name) = self.name_map(scan_position) {
+            let image_name = format!("{} - Image{:03}", name, i + 1);
+            let image = scan_position.images.get(&image_name);
+            if image.is_none() {
+                self.skip_unlinked_image(path, &format!("no image named {}", image_name));
+            }
+            image
         } else {
-            outfile.push(infile.as_ref().with_extension("las").file_name().unwrap());
+            let image = scan_position.images.get(&self.image_stem(path));
+            if image.is_none() && self.image_layout.contains("{scanpos}") {
+                self.skip_unlinked_image(
+                    path,
+                    &format!("no image in {} with that name", scan_position.name),
+                );
+            }
+            // Without the scan position in the layout, we see the images for every scan position.
+            image
         }
-        outfile
     }
 
-    fn name_map(&self, scan_position: &ScanPosition) -> Option<&str> {
-        self.name_map
-            .maps
+    /// Warns that an image file isn't one of its scan position's images, the first time, and
+    /// remembers it for the summary.
+    fn skip_unlinked_image(&self, path: &Path, message: &str) {
+        if self.unlinked_images.lock().unwrap().insert(path.to_path_buf()) {
+            warn!("could not link {} to an image, skipping it: {}", path.display(), message);
+        }
+    }
+
+    /// Returns the image files that couldn't be linked to images so far.
+    fn unlinked_images(&self) -> Vec<String> {
+        self.unlinked_images
+            .lock()
+            .unwrap()
             .iter()
-            .find(|map| map.from == scan_position.name)
-            .map(|map| map.to.as_str())
+            .map(|path| path.display().to_string())
+            .collect()
     }
-}
 
-impl fmt::Display for Config {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(f, "Configuration:")?;
-        writeln!(f, "  - project: {}", self.project.path.display())?;
-        writeln!(f, "  - image dir: {}", self.image_dir.display())?;
-        writeln!(f, "  - las dir: {}", self.las_dir.display())?;
-        writeln!(f, "  - scan positions:")?;
-        for scan_position in self.scan_positions() {
-            writeln!(f, "    - name: {}", scan_position.name)?;
-            let image_groups = self.image_groups(scan_position);
-            if image_groups.is_empty() {
-                writeln!(f, "    - no images for this scan position")?;
-            } else {
-                writeln!(f, "    - images:")?;
-                for image_group in image_groups {
-                    writeln!(f, "      - {}", image_group.irb_path.display())?;
+    /// Returns the configured image extension that matches this path, ignoring case.
+    fn image_extension(&self, path: &Path) -> Option<&str> {
+        let file_name = path.file_name()?.to_string_lossy().to_lowercase();
+        self.image_extensions
+            .iter()
+            .find(|extension| file_name.ends_with(&format!(".{}", extension)))
+            .map(|extension| extension.as_str())
+    }
+
+    /// Returns the file name of the image without its (possibly multi-part) extension.
+    fn image_stem(&self, path: &Path) -> String {
+        let file_name = path.file_name()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        match self.image_extension(path) {
+            Some(extension) => file_name[..file_name.len() - extension.len() - 1].to_string(),
+            None => file_name,
+        }
+    }
+
+    fn image_group<'a>(
+        &'a self,
+        scan_position: &'a ScanPosition,
+        image: &'a Image,
+        path: PathBuf,
+    ) -> Option<ImageGroup<'a>> {
+        if image_matches(&self.excluded_images, &path, image) ||
+            !self.only_images.is_empty() && !image_matches(&self.only_images, &path, image) ||
+            !self.is_selected_camera(image)
+        {
+            return None;
+        }
+        let temporary_file = if path.extension().map(|e| e.to_string_lossy().to_lowercase()) ==
+            Some("gz".to_string())
+        {
+            match TemporaryFile::gunzip(&path) {
+                Ok(temporary_file) => Some(temporary_file),
+                Err(err) => {
+                    self.skip_unreadable_image(&path, &format!("could not decompress: {}", err));
+                    return None;
                 }
             }
+        } else {
+            None
+        };
+        let irb_path = temporary_file
+            .as_ref()
+            .map(|temporary_file| temporary_file.0.as_path())
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .into_owned();
+        let irb = match self.retry.run(&format!("open {}", irb_path), || {
+            Irb::from_path(&irb_path)
+        }) {
+            Ok(irb) => irb,
+            Err(err) => {
+                self.skip_unreadable_image(&path, &format!("{:?}", err));
+                return None;
+            }
+        };
+        let camera_calibration = image
+            .camera_calibration(&self.project)
+            .expect("check_calibrations found every selected camera calibration");
+        let mount_calibration = self.mount_calibration(&path, image);
+        Some(ImageGroup {
+            atmosphere: self.atmosphere,
+            bad_pixel_mode: self.bad_pixel_mode,
+            bad_pixels: self.bad_pixels.get(&camera_calibration.name),
+            camera_calibration: camera_calibration,
+            decoded: None,
+            distortion: self.distortions.get(&camera_calibration.name),
+            emissivity: self.emissivity(scan_position, image),
+            flat_field: self.flat_fields.get(&camera_calibration.name),
+            frustum: Frustum::new(image, mount_calibration, camera_calibration),
+            image: image,
+            irb: irb,
+            mask: self.mask(&path),
+            irb_path: path,
+            mount_calibration: mount_calibration,
+            orientation: self.orientation(scan_position),
+            projection_lut: self.projection_luts.get(&camera_calibration.name),
+            reflected_temperature: self.reflected_temperature,
+            socs_to_cmcs: Affine::from_fn(|x, y, z| {
+                let cmcs = Point::socs(x, y, z).to_cmcs(image.cop, mount_calibration);
+                (cmcs.x, cmcs.y, cmcs.z)
+            }),
+            valid_temperatures: self.valid_temperatures,
+            temporary_file: temporary_file,
+        })
+    }
+
+    /// Warns that an image can't be opened, the first time, and remembers it for the summary.
+    fn skip_unreadable_image(&self, path: &Path, message: &str) {
+        if self.unreadable_images.lock().unwrap().insert(path.to_path_buf()) {
+            warn!("could not read {}, skipping it: {}", path.display(), message);
         }
+    }
 
-        Ok(())
+    /// Returns the images that couldn't be opened so far.
+    fn unreadable_images(&self) -> Vec<String> {
+        self.unreadable_images
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect()
     }
-}
 
-impl<'a> ImageGroup<'a> {
-    fn temperature(&self, socs: &Point<Socs>) -> Option<f64> {
-        let cmcs = socs.to_cmcs(self.image.cop, self.mount_calibration);
-        self.camera_calibration.cmcs_to_ics(&cmcs).map(|(mut u,
-          mut v)| {
-            if self.rotate {
-                let new_u = self.camera_calibration.height as f64 - v;
-                v = u;
-                u = new_u;
-            }
-            self.irb
-                .temperature(u.trunc() as i32, v.trunc() as i32)
-                .expect("error when retrieving temperature") - 273.15
-        })
+    /// Returns true if the image was taken with one of the `--camera` calibrations, or if no
+    /// cameras were selected.
+    fn is_selected_camera(&self, image: &Image) -> bool {
+        self.cameras.is_empty() || self.cameras.contains(&image.camera_calibration_name)
+    }
+
+    /// Returns the image's mount calibration, which is the `--tilt-mount` for `--tilt-images`.
+    ///
+    /// The image must be of a selected camera, since only those are checked up front.
+    fn mount_calibration(&self, path: &Path, image: &Image) -> &MountCalibration {
+        match self.tilt_mount.as_ref() {
+            Some(tilt_mount) if image_matches(&self.tilt_images, path, image) => {
+                &self.project.mount_calibrations[tilt_mount]
+            }
+            _ => image
+                .mount_calibration(&self.project)
+                .expect("check_calibrations found every selected mount calibration"),
+        }
+    }
+
+    /// Returns the extension of the output files, `laz` with `--laz` and `las` otherwise.
+    fn output_extension(&self) -> &'static str {
+        if self.laz {
+            "laz"
+        } else {
+            "las"
+        }
+    }
+
+    /// Returns the output file for an input file.
+    ///
+    /// With `--use-scanpos-names` and `--number-scans`, the `number` of the scan is appended to
+    /// the scan position name, e.g. `SP01_2.las`.
+    fn outfile<P: AsRef<Path>>(
+        &self,
+        scan_position: &ScanPosition,
+        infile: P,
+        number: Option<usize>,
+    ) -> PathBuf {
+        let mut outfile = self.las_dir.clone();
+        if self.output_layout == OutputLayout::PerScanPosition {
+            outfile.push(&scan_position.name);
+        }
+        if self.use_scanpos_names {
+            let name = match number {
+                Some(number) => format!("{}_{}", scan_position.name, number),
+                None => scan_position.name.clone(),
+            };
+            outfile.push(Path::new(&name).with_extension(self.output_extension()));
+        } else {
+            outfile.push(
+                infile
+                    .as_ref()
+                    .with_extension(self.output_extension())
+                    .file_name()
+                    .unwrap(),
+            );
+        }
+        outfile
+    }
+
+    /// Returns true if the image was captured close enough to the scan.
+    ///
+    /// Always true if no `--max-time-offset` was provided.
+    fn is_within_time_offset(&self, irb_path: &Path, rxp_path: &Path) -> bool {
+        let max_time_offset = match self.max_time_offset {
+            Some(max_time_offset) => max_time_offset,
+            None => return true,
+        };
+        let image_time = self.image_time_format.as_ref().and_then(|format| {
+            parse_time(&self.image_stem(irb_path), format)
+        });
+        let scan_time = rxp_path.file_stem().and_then(|file_stem| {
+            parse_time(&file_stem.to_string_lossy(), &self.scan_time_format)
+        });
+        match (image_time, scan_time) {
+            (Some(image_time), Some(scan_time)) => {
+                let offset = image_time.signed_duration_since(scan_time);
+                offset <= max_time_offset && -offset <= max_time_offset
+            }
+            (None, _) => {
+                warn!(
+                    "could not parse a timestamp from {}, skipping",
+                    irb_path.display()
+                );
+                false
+            }
+            (_, None) => {
+                warn!(
+                    "could not parse a timestamp from {}, skipping all images",
+                    rxp_path.display()
+                );
+                false
+            }
+        }
+    }
+
+    fn orientation(&self, scan_position: &ScanPosition) -> Orientation {
+        let mut orientation = self.orientation;
+        if let Some(&rotation) = self.position_rotations.get(&scan_position.name) {
+            orientation.rotation = rotation;
+        }
+        orientation
+    }
+
+    fn mask(&self, irb_path: &Path) -> Option<Mask> {
+        self.mask_dir.as_ref().and_then(|mask_dir| {
+            let mut path = mask_dir.clone();
+            path.push(format!("{}.png", self.image_stem(irb_path)));
+            if path.exists() {
+                Some(Mask::from_png(&path).expect(
+                    &format!("Could not read mask {}", path.display()),
+                ))
+            } else {
+                None
+            }
+        })
+    }
+
+    fn name_map(&self, scan_position: &ScanPosition) -> Option<&str> {
+        self.name_map
+            .maps
+            .iter()
+            .find(|map| map.from == scan_position.name)
+            .map(|map| map.to.as_str())
+    }
+
+    fn emissivity(&self, scan_position: &ScanPosition, image: &Image) -> f64 {
+        let emissivities = self.emissivity_map.emissivities.iter().filter(|emissivity| {
+            emissivity.scan_position == scan_position.name
+        });
+        let mut scan_position_emissivity = None;
+        for emissivity in emissivities {
+            match emissivity.image {
+                Some(ref name) if *name == image.name => return emissivity.emissivity,
+                Some(_) => {}
+                None => scan_position_emissivity = Some(emissivity.emissivity),
+            }
+        }
+        scan_position_emissivity.unwrap_or(self.emissivity)
+    }
+}
+
+/// Returns the path, size, and modification time of each file, sorted by path.
+fn file_fingerprint<I>(paths: I) -> Vec<(PathBuf, u64, Option<std::time::SystemTime>)>
+where
+    I: IntoIterator<Item = PathBuf>,
+{
+    let mut fingerprint = paths
+        .into_iter()
+        .map(|path| {
+            let metadata = fs::metadata(&path).ok();
+            (
+                path,
+                metadata.as_ref().map(|metadata| metadata.len()).unwrap_or(0),
+                metadata.and_then(|metadata| metadata.modified().ok()),
+            )
+        })
+        .collect::<Vec<_>>();
+    fingerprint.sort();
+    fingerprint
+}
+
+/// Adds a suffix to an outfile's stem, e.g. `SP01-rgb.las` for `SP01.las`.
+fn with_suffix(outfile: &Path, suffix: &str) -> PathBuf {
+    let file_stem = outfile.file_stem().unwrap().to_string_lossy();
+    let extension = outfile.extension().unwrap().to_string_lossy();
+    outfile.with_file_name(format!("{}-{}.{}", file_stem, suffix, extension))
+}
+
+/// Returns where a `--laz` outfile is written before it's compressed, e.g. `SP01.laz.las` for
+/// `SP01.laz`.
+fn uncompressed(outfile: &Path) -> PathBuf {
+    outfile.with_extension("laz.las")
+}
+
+/// Reads a csv file of `irb file, scan position, image name` rows.
+///
+/// Relative irb paths are relative to the image directory. Blank lines and lines starting with `#`
+/// are ignored.
+fn read_image_map(path: &str) -> std::io::Result<Vec<ImageMapEntry>> {
+    use std::fs::File;
+    use std::io::{BufRead, BufReader, Error, ErrorKind};
+
+    let mut entries = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line.split(',').map(|field| field.trim()).collect::<Vec<_>>();
+        if fields.len() != 3 {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("expected irb file, scan position, and image name, got: {}", line),
+            ));
+        }
+        entries.push(ImageMapEntry {
+            path: PathBuf::from(fields[0]),
+            scan_position: fields[1].to_string(),
+            image: fields[2].to_string(),
+        });
+    }
+    Ok(entries)
+}
+
+/// Reads camera calibrations from a JSON file of one object, or an array of objects, with the
+/// fields of `CameraCalibration`.
+///
+/// Each object's `name` must be a camera calibration in the project, and fields that aren't
+/// provided are taken from the project's calibration, so e.g. only the intrinsics can be replaced.
+fn read_camera_calibrations(
+    path: &str,
+    project: &Project,
+) -> std::io::Result<Vec<CameraCalibration>> {
+    use serde_json::Value;
+    use std::fs::File;
+    use std::io::{Error, ErrorKind};
+
+    let invalid = |message: String| Error::new(ErrorKind::InvalidData, message);
+    let value: Value = serde_json::from_reader(File::open(path)?)
+        .map_err(|err| invalid(err.to_string()))?;
+    let objects = match value {
+        Value::Array(objects) => objects,
+        object => vec![object],
+    };
+    let mut camera_calibrations = Vec::new();
+    for object in objects {
+        let name = object["name"]
+            .as_str()
+            .ok_or_else(|| invalid("each camera calibration needs a name".to_string()))?;
+        let mut merged = project
+            .camera_calibrations
+            .get(name)
+            .map(|camera_calibration| json!(camera_calibration))
+            .ok_or_else(|| {
+                invalid(format!("no camera calibration named {} in the project", name))
+            })?;
+        for (key, value) in object.as_object().into_iter().flat_map(|object| object.iter()) {
+            if merged.get(key).is_none() {
+                return Err(invalid(format!("unknown camera calibration field: {}", key)));
+            }
+            merged[key] = value.clone();
+        }
+        let float = |key: &str| {
+            merged[key]
+                .as_f64()
+                .ok_or_else(|| invalid(format!("{} must be a number", key)))
+        };
+        let size = |key: &str| {
+            merged[key]
+                .as_u64()
+                .map(|n| n as usize)
+                .ok_or_else(|| invalid(format!("{} must be a whole number", key)))
+        };
+        camera_calibrations.push(CameraCalibration {
+            name: name.to_string(),
+            cx: float("cx")?,
+            cy: float("cy")?,
+            fx: float("fx")?,
+            fy: float("fy")?,
+            k1: float("k1")?,
+            k2: float("k2")?,
+            k3: float("k3")?,
+            k4: float("k4")?,
+            p1: float("p1")?,
+            p2: float("p2")?,
+            tan_max_horz: float("tan_max_horz")?,
+            tan_max_vert: float("tan_max_vert")?,
+            tan_min_horz: float("tan_min_horz")?,
+            tan_min_vert: float("tan_min_vert")?,
+            width: size("width")?,
+            height: size("height")?,
+        });
+    }
+    Ok(camera_calibrations)
+}
+
+fn parse_time(file_stem: &str, format: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(file_stem, format).ok()
+}
+
+/// Returns the names of one `--scan-position` value.
+///
+/// `@path` reads newline-separated names from a file, and `-` reads them from stdin.
+fn scan_position_names(value: &str) -> Vec<String> {
+    use std::fs::File;
+    use std::io::Read;
+
+    let mut s = String::new();
+    if value == "-" {
+        std::io::stdin()
+            .read_to_string(&mut s)
+            .expect("Could not read scan position names from stdin");
+    } else if value.starts_with('@') {
+        File::open(&value[1..])
+            .and_then(|mut file| file.read_to_string(&mut s))
+            .expect(&format!("Could not read scan position names from {}", &value[1..]));
+    } else {
+        return vec![value.to_string()];
+    }
+    s.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect()
+}
+
+fn patterns(matches: &ArgMatches, name: &str) -> Vec<Pattern> {
+    matches
+        .values_of(name)
+        .map(|values| {
+            values
+                .map(|value| {
+                    Pattern::new(value).expect(&format!(
+                        "Invalid pattern for --{}: {}",
+                        name,
+                        value
+                    ))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Replaces the project's camera calibrations with the `--camera-calibration` overrides.
+fn override_camera_calibrations(matches: &ArgMatches, project: &mut Project) {
+    for path in matches
+        .values_of("camera-calibration")
+        .map(|values| values.collect())
+        .unwrap_or_else(Vec::new)
+    {
+        let camera_calibrations = read_camera_calibrations(path, project).expect(&format!(
+            "Could not read camera calibration {}",
+            path
+        ));
+        for camera_calibration in camera_calibrations {
+            project
+                .camera_calibrations
+                .insert(camera_calibration.name.clone(), camera_calibration);
+        }
+    }
+}
+
+/// Returns the name of the mount calibration for `--tilt-images`, if there are any.
+fn tilt_mount(matches: &ArgMatches, project: &Project) -> Option<String> {
+    if matches.is_present("tilt-mount") && !matches.is_present("tilt-images") {
+        panic!("--tilt-mount requires --tilt-images");
+    }
+    if !matches.is_present("tilt-images") {
+        return None;
+    }
+    let tilt_mount = match matches.value_of("tilt-mount") {
+        Some(name) => name.to_string(),
+        None => {
+            let mut names = project
+                .mount_calibrations
+                .keys()
+                .filter(|name| name.to_lowercase().contains("tilt"))
+                .collect::<Vec<_>>();
+            if names.len() != 1 {
+                panic!(
+                    "--tilt-images needs --tilt-mount, since the project has {} mount \
+                     calibrations with \"tilt\" in their name",
+                    names.len()
+                );
+            }
+            names.pop().unwrap().clone()
+        }
+    };
+    if !project.mount_calibrations.contains_key(&tilt_mount) {
+        panic!("--tilt-mount: no mount calibration named {} in the project", tilt_mount);
+    }
+    Some(tilt_mount)
+}
+
+/// Returns the orientation of the image files from `--rotation` (or the deprecated `--rotate`),
+/// `--flip-h`, and `--flip-v`.
+fn orientation(matches: &ArgMatches) -> Orientation {
+    let rotation = if matches.is_present("rotate") {
+        warn!("--rotate is deprecated, use --rotation 90");
+        Rotation::R90
+    } else {
+        value_t!(matches, "rotation", Rotation).unwrap()
+    };
+    Orientation {
+        flip_horizontal: matches.is_present("flip-h"),
+        flip_vertical: matches.is_present("flip-v"),
+        rotation: rotation,
+    }
+}
+
+/// Returns the `--rotate-position` rotations, by scan position name.
+fn position_rotations(matches: &ArgMatches, project: &Project) -> HashMap<String, Rotation> {
+    matches
+        .values_of("rotate-position")
+        .map(|values| {
+            values
+                .map(|value| {
+                    let mut iter = value.rsplitn(2, ':');
+                    let rotation = iter.next().unwrap();
+                    let name = iter.next().expect(&format!(
+                        "--rotate-position must be NAME:ANGLE, got {}",
+                        value
+                    ));
+                    if !project.scan_positions.contains_key(name) {
+                        panic!("--rotate-position: no scan position named {}", name);
+                    }
+                    (name.to_string(), rotation.parse::<Rotation>().unwrap())
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Returns true if any of the patterns match the image's file name, file stem, or project name.
+fn image_matches(patterns: &[Pattern], path: &Path, image: &Image) -> bool {
+    let names = [
+        path.file_name().map(|s| s.to_string_lossy().into_owned()),
+        path.file_stem().map(|s| s.to_string_lossy().into_owned()),
+        Some(image.name.clone()),
+    ];
+    patterns.iter().any(|pattern| {
+        names.iter().filter_map(|name| name.as_ref()).any(
+            |name| pattern.matches(name),
+        )
+    })
+}
+
+fn per_camera<T, F>(dir: &str, read: F) -> HashMap<String, T>
+where
+    F: Fn(&Path) -> std::io::Result<T>,
+{
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            let camera = path.file_stem().unwrap().to_string_lossy().into_owned();
+            let value = read(&path).expect(&format!("Could not read {}", path.display()));
+            (camera, value)
+        })
+        .collect()
+}
+
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Configuration:")?;
+        writeln!(f, "  - project: {}", self.project.path.display())?;
+        if self.pop_overridden {
+            writeln!(
+                f,
+                "  - pop override, translation: ({}, {}, {})",
+                self.project.pop[(0, 3)],
+                self.project.pop[(1, 3)],
+                self.project.pop[(2, 3)]
+            )?;
+        }
+        if let Some(projection_lut) = self.projection_luts.values().next() {
+            writeln!(
+                f,
+                "  - projection lookup tables: {}° resolution",
+                projection_lut.resolution()
+            )?;
+        }
+        if let Some(sop_file) = self.sop_file.as_ref() {
+            writeln!(f, "  - sop overrides: {}", sop_file.display())?;
+        }
+        for path in &self.camera_calibration_files {
+            writeln!(f, "  - camera calibration override: {}", path.display())?;
+        }
+        if !self.cameras.is_empty() {
+            writeln!(f, "  - cameras: {}", self.cameras.join(", "))?;
+        }
+        if let Some((ref path, _)) = self.transform {
+            writeln!(f, "  - output transform: {}", path.display())?;
+        }
+        match self.sync_to_pps {
+            Some(sync_to_pps) => writeln!(f, "  - sync to pps: {}", sync_to_pps)?,
+            None => writeln!(f, "  - sync to pps: if the rxp file has synced points")?,
+        }
+        if self.epochs.is_empty() {
+            writeln!(f, "  - image dir: {}", self.image_dir.display())?;
+        } else {
+            for epoch in &self.epochs {
+                writeln!(f, "  - epoch {}: {}", epoch.name, epoch.image_dir.display())?;
+            }
+        }
+        if let Some(image_map) = self.image_map.as_ref() {
+            writeln!(f, "  - image map: {} images", image_map.len())?;
+        } else {
+            writeln!(f, "  - image layout: {}", self.image_layout)?;
+            writeln!(f, "  - image extensions: {}", self.image_extensions.join(", "))?;
+        }
+        if let Some(input_dir) = self.input_dir.as_ref() {
+            writeln!(
+                f,
+                "  - input dir: {} ({:?})",
+                input_dir.display(),
+                self.input_frame
+            )?;
+        }
+        writeln!(
+            f,
+            "  - las dir: {} ({:?})",
+            self.las_dir.display(),
+            self.output_layout
+        )?;
+        writeln!(
+            f,
+            "  - las version {}.{}, point format {}",
+            self.las_version.0,
+            self.las_version.1,
+            self.point_format
+        )?;
+        if self.laz {
+            match self.laz_chunk_size {
+                Some(chunk_size) => {
+                    writeln!(f, "  - compressed to LAZ, chunk size {}", chunk_size)?
+                }
+                None => writeln!(f, "  - compressed to LAZ")?,
+            }
+        }
+        if let Some((min, max)) = self.nir_temperature {
+            writeln!(f, "  - nir temperature domain: {} to {}", min, max)?;
+        }
+        writeln!(f, "  - las scale {}, offset {:?}", self.scale, self.offset)?;
+        if self.valid_temperatures != (std::f64::NEG_INFINITY, std::f64::INFINITY) {
+            writeln!(
+                f,
+                "  - valid pixel temperatures: {} to {} °C",
+                self.valid_temperatures.0,
+                self.valid_temperatures.1
+            )?;
+        }
+        if self.mmap_images {
+            writeln!(f, "  - images decoded into memory-mapped files")?;
+        }
+        if self.memory.is_limited() {
+            writeln!(
+                f,
+                "  - memory budget: {} points per chunk, {} bytes per output buffer",
+                self.chunk_len(),
+                self.memory
+                    .buffer_len(self.writers_per_translation(), WRITE_BUFFER_BYTES)
+            )?;
+        }
+        if self.min_images > 1 {
+            writeln!(f, "  - min images per temperature: {}", self.min_images)?;
+        }
+        if let Some(max_temperature_spread) = self.max_temperature_spread {
+            writeln!(f, "  - max temperature spread: {} °C", max_temperature_spread)?;
+        }
+        if let Some(max_time_offset) = self.max_time_offset {
+            writeln!(
+                f,
+                "  - max time offset: {} minutes",
+                max_time_offset.num_seconds() as f64 / 60.
+            )?;
+        }
+        if !self.only_images.is_empty() {
+            let patterns = self.only_images
+                .iter()
+                .map(|pattern| pattern.as_str())
+                .collect::<Vec<_>>();
+            writeln!(f, "  - only images: {}", patterns.join(", "))?;
+        }
+        if let Some(tilt_mount) = self.tilt_mount.as_ref() {
+            let patterns = self.tilt_images
+                .iter()
+                .map(|pattern| pattern.as_str())
+                .collect::<Vec<_>>();
+            writeln!(f, "  - tilt mount {}: {}", tilt_mount, patterns.join(", "))?;
+        }
+        if !self.scans.is_empty() {
+            let patterns = self.scans
+                .iter()
+                .map(|pattern| pattern.as_str())
+                .collect::<Vec<_>>();
+            writeln!(f, "  - scans: {}", patterns.join(", "))?;
+        }
+        if !self.excluded_images.is_empty() {
+            let patterns = self.excluded_images
+                .iter()
+                .map(|pattern| pattern.as_str())
+                .collect::<Vec<_>>();
+            writeln!(f, "  - excluded images: {}", patterns.join(", "))?;
+        }
+        if let Some(mask_dir) = self.mask_dir.as_ref() {
+            writeln!(f, "  - mask dir: {}", mask_dir.display())?;
+        }
+        if let Some(kml) = self.kml.as_ref() {
+            writeln!(f, "  - kml: {} (UTM zone {:?})", kml.display(), self.utm_zone.unwrap())?;
+        }
+        if let Some(geojson) = self.geojson.as_ref() {
+            writeln!(f, "  - geojson: {}", geojson.display())?;
+        }
+        if let Some(upload) = self.upload.as_ref() {
+            writeln!(f, "  - output url: {} (local copies are removed)", upload)?;
+        }
+        if let Some(preview_width) = self.preview_width {
+            writeln!(f, "  - preview: {} pixels wide", preview_width)?;
+        }
+        if let Some(ortho_resolution) = self.ortho_resolution {
+            writeln!(
+                f,
+                "  - orthomosaic: {}m {:?} temperature{}",
+                ortho_resolution,
+                self.ortho_statistic,
+                if self.merge_ortho { ", merged" } else { "" }
+            )?;
+        }
+        for &(ref path, ref ortho) in &self.thermal_orthos {
+            writeln!(
+                f,
+                "  - thermal orthophoto: {} ({}x{}, {}m pixels)",
+                path.display(),
+                ortho.width,
+                ortho.height,
+                ortho.scale.0
+            )?;
+        }
+        if let Some(delta_dir) = self.delta_dir.as_ref() {
+            writeln!(f, "  - delta baseline image dir: {}", delta_dir.display())?;
+        }
+        if let Some(rgb_dir) = self.rgb_dir.as_ref() {
+            writeln!(f, "  - rgb dir: {}", rgb_dir.display())?;
+            if self.dual_output {
+                writeln!(f, "  - dual output: thermal and rgb las files")?;
+            }
+        }
+        if !self.split_by_temperature.is_empty() {
+            writeln!(
+                f,
+                "  - split by temperature at: {}",
+                self.split_by_temperature
+                    .iter()
+                    .map(|temperature_break| temperature_break.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )?;
+        }
+        writeln!(f, "  - orientation: {}", self.orientation)?;
+        writeln!(f, "  - emissivity: {}", self.emissivity)?;
+        if let Some(atmosphere) = self.atmosphere {
+            writeln!(
+                f,
+                "  - atmosphere: {}°C, {}% relative humidity",
+                atmosphere.temperature - 273.15,
+                atmosphere.relative_humidity * 100.
+            )?;
+        }
+        if !self.flat_fields.is_empty() {
+            let mut cameras = self.flat_fields.keys().map(|s| s.as_str()).collect::<Vec<_>>();
+            cameras.sort();
+            writeln!(f, "  - flat fields: {}", cameras.join(", "))?;
+        }
+        if !self.distortions.is_empty() {
+            let mut cameras = self.distortions.keys().map(|s| s.as_str()).collect::<Vec<_>>();
+            cameras.sort();
+            writeln!(f, "  - supplemental lens distortion: {}", cameras.join(", "))?;
+        }
+        if !self.bad_pixels.is_empty() {
+            let mut cameras = self.bad_pixels.keys().map(|s| s.as_str()).collect::<Vec<_>>();
+            cameras.sort();
+            writeln!(
+                f,
+                "  - bad pixel maps ({:?}): {}",
+                self.bad_pixel_mode,
+                cameras.join(", ")
+            )?;
+        }
+        if !self.emissivity_map.emissivities.is_empty() {
+            writeln!(
+                f,
+                "  - emissivity overrides: {}",
+                self.emissivity_map.emissivities.len()
+            )?;
+        }
+        writeln!(f, "  - scan positions:")?;
+        for scan_position in self.scan_positions() {
+            writeln!(f, "    - name: {}", scan_position.name)?;
+            if let Some(alias) = self.scan_position_aliases.get(&scan_position.name) {
+                writeln!(f, "    - alias: {}", alias)?;
+            }
+            if self.position_rotations.contains_key(&scan_position.name) {
+                writeln!(f, "    - orientation: {}", self.orientation(scan_position))?;
+            }
+            for (epoch, image_dir) in self.image_dirs() {
+                let image_groups = self.image_groups(scan_position, image_dir);
+                let epoch = epoch.map(|epoch| format!(" ({})", epoch)).unwrap_or_default();
+                if image_groups.is_empty() {
+                    writeln!(f, "    - no images{} for this scan position", epoch)?;
+                } else {
+                    writeln!(f, "    - images{}:", epoch)?;
+                    for image_group in image_groups {
+                        if image_group.mask.is_some() {
+                            writeln!(f, "      - {} (masked)", image_group.irb_path.display())?;
+                        } else {
+                            writeln!(f, "      - {}", image_group.irb_path.display())?;
+                        }
+                    }
+                }
+            }
+            let rgb_image_groups = self.rgb_image_groups(scan_position);
+            if !rgb_image_groups.is_empty() {
+                writeln!(f, "    - rgb images:")?;
+                for rgb_image_group in rgb_image_groups {
+                    writeln!(f, "      - {}", rgb_image_group.path.display())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> ImageGroup<'a> {
+    fn temperature(&self, socs: &Point<Socs>) -> Option<f64> {
+        self.pixel(socs).and_then(|pixel| self.temperature_at(pixel, socs))
+    }
+
+    /// Returns the temperature of a pixel that `socs` has already been projected into.
+    ///
+    /// Pixels whose recorded temperatures are outside the valid temperatures, e.g. because the
+    /// detector was saturated, don't have a temperature.
+    fn temperature_at(&self, (x, y): (i32, i32), socs: &Point<Socs>) -> Option<f64> {
+        let (min, max) = self.valid_temperatures;
+        self.sample(x, y)
+            .filter(|&temperature| {
+                let celsius = temperature - 273.15;
+                celsius >= min && celsius <= max
+            })
+            .map(|temperature| {
+                let range = (socs.x.powi(2) + socs.y.powi(2) + socs.z.powi(2)).sqrt();
+                self.correct(temperature, range) - 273.15
+            })
+    }
+
+    /// Returns the pixel of the image file that this point projects into.
+    ///
+    /// Points outside of the image's frustum are rejected before they're projected. With a
+    /// `--projection-lut`, the pixel is interpolated from the lookup table unless the point is near
+    /// the edge of the image.
+    fn pixel(&self, socs: &Point<Socs>) -> Option<(i32, i32)> {
+        if !self.frustum.contains(socs) {
+            return None;
+        }
+        self.cmcs_pixel(&socs.to_cmcs(self.image.cop, self.mount_calibration))
+    }
+
+    /// Returns the pixel of the image file that this point, already in the camera's coordinate
+    /// system, projects into.
+    fn cmcs_pixel(&self, cmcs: &Point<Cmcs>) -> Option<(i32, i32)> {
+        if cmcs.is_behind_camera() {
+            return None;
+        }
+        match self.projection_lut.map(|projection_lut| projection_lut.get(cmcs)) {
+            Some(Lookup::Pixel(u, v)) => {
+                Some(self.orientation.pixel(
+                    u,
+                    v,
+                    self.camera_calibration.width as i32,
+                    self.camera_calibration.height as i32,
+                ))
+            }
+            Some(Lookup::Outside) => None,
+            Some(Lookup::Unknown) | None => {
+                file_pixel(cmcs, self.camera_calibration, self.distortion, &self.orientation)
+            }
+        }
+    }
+
+    /// Returns the width and height of the image file, which might be rotated.
+    fn file_dimensions(&self) -> (usize, usize) {
+        let (width, height) = (
+            self.camera_calibration.width as usize,
+            self.camera_calibration.height as usize,
+        );
+        match self.orientation.rotation {
+            Rotation::R0 | Rotation::R180 => (width, height),
+            Rotation::R90 | Rotation::R270 => (height, width),
+        }
+    }
+
+    /// Writes the number of points that hit each pixel as a png.
+    ///
+    /// Brightness is scaled to the most-hit pixel, and any pixel with a hit is at least slightly
+    /// brighter than black.
+    fn write_coverage(&self, path: &Path, coverage: &[u32]) {
+        let (width, height) = self.file_dimensions();
+        let max = coverage.iter().cloned().max().unwrap_or(0).max(1);
+        let data = coverage
+            .iter()
+            .map(|&count| if count == 0 {
+                0
+            } else {
+                ((255 * u64::from(count)) / u64::from(max)).max(1) as u8
+            })
+            .collect::<Vec<_>>();
+        raster::write_png(path, width, height, png::ColorType::Grayscale, &data)
+            .expect(&format!("Could not write coverage {}", path.display()));
+    }
+
+    /// Logs the minimum, maximum, and mean temperature of every (unmasked) pixel, before
+    /// atmospheric correction, in °C.
+    fn log_stats(&self) {
+        let (width, height) = self.file_dimensions();
+        let (mut min, mut max, mut sum, mut count) = (f64::INFINITY, f64::NEG_INFINITY, 0., 0);
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                if let Some(temperature) = self.sample(x, y) {
+                    let temperature = temperature - 273.15;
+                    min = min.min(temperature);
+                    max = max.max(temperature);
+                    sum += temperature;
+                    count += 1;
+                }
+            }
+        }
+        let path = self.irb_path.display().to_string();
+        if count == 0 {
+            warn!("{} has no usable pixels", path);
+            return;
+        }
+        let mean = sum / count as f64;
+        info!(
+            "    - {}: min {:.1}, max {:.1}, mean {:.1} °C",
+            path,
+            min,
+            max,
+            mean
+        );
+        logger::event(json!({
+            "event": "image_stats",
+            "path": path,
+            "min": min,
+            "max": max,
+            "mean": mean,
+        }));
+    }
+
+    /// Returns the temperature of a pixel, or `None` if it's masked, a skipped bad pixel, or
+    /// couldn't be read.
+    fn sample(&self, x: i32, y: i32) -> Option<f64> {
+        if self.mask.as_ref().map(|mask| mask.contains(x, y)).unwrap_or(false) {
+            None
+        } else if self.is_bad_pixel(x, y) {
+            match self.bad_pixel_mode {
+                BadPixelMode::Inpaint => self.inpaint(x, y),
+                BadPixelMode::Skip => None,
+            }
+        } else {
+            self.raw_temperature(x, y)
+        }
+    }
+
+    /// Decodes every pixel of the image into memory, so samples don't go through the irb handle.
+    fn preload(&mut self) {
+        let decoded = self.decode().collect::<Vec<_>>();
+        self.decoded = Some(Decoded::Memory(decoded));
+    }
+
+    /// Decodes every pixel of the image into a memory-mapped temporary file.
+    fn preload_mapped(&mut self) -> std::io::Result<()> {
+        let decoded = Decoded::mapped(self.decode())?;
+        self.decoded = Some(decoded);
+        Ok(())
+    }
+
+    /// Reads the temperature of every pixel, row by row, with NaN for pixels that can't be read.
+    fn decode<'b>(&'b self) -> Box<Iterator<Item = f32> + 'b> {
+        use std::f32;
+
+        let (width, height) = self.file_dimensions();
+        Box::new((0..height as i32).flat_map(move |y| {
+            (0..width as i32).map(move |x| {
+                self.irb
+                    .temperature(x, y)
+                    .map(|temperature| temperature as f32)
+                    .unwrap_or(f32::NAN)
+            })
+        }))
+    }
+
+    /// Returns the temperature of a pixel, from memory if the image was preloaded.
+    fn decoded_temperature(&self, x: i32, y: i32) -> Option<f64> {
+        let decoded = match self.decoded.as_ref() {
+            Some(decoded) => decoded,
+            None => return self.irb.temperature(x, y).ok(),
+        };
+        let (width, height) = self.file_dimensions();
+        if x < 0 || y < 0 || x as usize >= width || y as usize >= height {
+            return None;
+        }
+        let temperature = decoded.get(y as usize * width + x as usize);
+        if temperature.is_nan() {
+            None
+        } else {
+            Some(f64::from(temperature))
+        }
+    }
+
+    fn raw_temperature(&self, x: i32, y: i32) -> Option<f64> {
+        self.decoded_temperature(x, y).map(|temperature| {
+            temperature *
+                self.flat_field
+                    .and_then(|flat_field| flat_field.get(x, y))
+                    .unwrap_or(1.)
+        })
+    }
+
+    fn is_bad_pixel(&self, x: i32, y: i32) -> bool {
+        self.bad_pixels
+            .map(|bad_pixels| bad_pixels.contains(x, y))
+            .unwrap_or(false)
+    }
+
+    /// Replaces a bad pixel with the mean of its good neighbors.
+    fn inpaint(&self, x: i32, y: i32) -> Option<f64> {
+        let mut neighbors = Vec::new();
+        for dy in -1..2 {
+            for dx in -1..2 {
+                if (dx, dy) == (0, 0) || self.is_bad_pixel(x + dx, y + dy) {
+                    continue;
+                }
+                if let Some(temperature) = self.raw_temperature(x + dx, y + dy) {
+                    neighbors.push(temperature);
+                }
+            }
+        }
+        if neighbors.is_empty() {
+            None
+        } else {
+            Some(neighbors.iter().sum::<f64>() / neighbors.len() as f64)
+        }
+    }
+
+    /// Corrects a radiometric temperature (in Kelvin) that was captured assuming an emissivity of
+    /// one and a perfectly transparent atmosphere.
+    ///
+    /// The camera sees the radiation emitted by the object, the radiation reflected off of it, and
+    /// the radiation emitted by the atmosphere between the two. We remove the atmospheric and
+    /// reflected components and scale by the emissivity, using Stefan-Boltzmann (T⁴) as a proxy
+    /// for the radiance.
+    fn correct(&self, temperature: f64, range: f64) -> f64 {
+        let transmission = self.atmosphere
+            .map(|atmosphere| atmosphere.transmission(range))
+            .unwrap_or(1.);
+        if self.emissivity == 1. && transmission == 1. {
+            return temperature;
+        }
+        let mut observed = temperature.powi(4) -
+            transmission * (1. - self.emissivity) * self.reflected_temperature.powi(4);
+        if let Some(atmosphere) = self.atmosphere {
+            observed -= (1. - transmission) * atmosphere.temperature.powi(4);
+        }
+        (observed.max(0.) / (transmission * self.emissivity)).powf(0.25)
+    }
+}
+
+impl<'a> RgbImageGroup<'a> {
+    fn color(&self, socs: &Point<Socs>) -> Option<Color> {
+        use image::Pixel;
+
+        project(socs, self.image, self.mount_calibration, self.camera_calibration).and_then(
+            |(u, v)| {
+                let (x, y) = (u.trunc(), v.trunc());
+                if x < 0. || y < 0. || x >= self.rgb.width() as f64 ||
+                    y >= self.rgb.height() as f64
+                {
+                    return None;
+                }
+                let channels = self.rgb.get_pixel(x as u32, y as u32).channels();
+                Some(Color {
+                    red: u16::from(channels[0]) * 257,
+                    green: u16::from(channels[1]) * 257,
+                    blue: u16::from(channels[2]) * 257,
+                })
+            },
+        )
+    }
+}
+
+/// Projects a scanner's own coordinate system point into an image's pixel coordinates.
+///
+/// Returns `None` if the point is not visible in the image.
+fn project(
+    socs: &Point<Socs>,
+    image: &Image,
+    mount_calibration: &MountCalibration,
+    camera_calibration: &CameraCalibration,
+) -> Option<(f64, f64)> {
+    let cmcs = socs.to_cmcs(image.cop, mount_calibration);
+    camera_calibration.cmcs_to_ics(&cmcs)
+}
+
+/// Projects a point in the camera's coordinate system into the image's pixel coordinates, with any
+/// supplemental lens distortion.
+///
+/// Returns `None` if the point is not visible in the image.
+fn project_cmcs(
+    cmcs: &Point<Cmcs>,
+    camera_calibration: &CameraCalibration,
+    distortion: Option<&Distortion>,
+) -> Option<(f64, f64)> {
+    camera_calibration
+        .cmcs_to_ics(cmcs)
+        .and_then(|(u, v)| match distortion {
+            Some(distortion) => distortion.apply(u, v, camera_calibration),
+            None => Some((u, v)),
+        })
+}
+
+/// Returns the pixel of an image file that a point in the camera's coordinate system projects into,
+/// with any supplemental lens distortion and the file's orientation.
+///
+/// This is the projection that points are colorized with, unless there's a `--projection-lut`.
+fn file_pixel(
+    cmcs: &Point<Cmcs>,
+    camera_calibration: &CameraCalibration,
+    distortion: Option<&Distortion>,
+    orientation: &Orientation,
+) -> Option<(i32, i32)> {
+    if cmcs.is_behind_camera() {
+        return None;
+    }
+    project_cmcs(cmcs, camera_calibration, distortion).map(|(u, v)| {
+        orientation.pixel(
+            u,
+            v,
+            camera_calibration.width as i32,
+            camera_calibration.height as i32,
+        )
+    })
+}
+
+impl TemporaryFile {
+    /// Decompresses a gzipped file into the system's temporary directory.
+    ///
+    /// The same image can be decompressed more than once at a time, e.g. from IMAGE_DIR and the
+    /// `--delta-dir`, so each file gets a unique name.
+    fn gunzip(path: &Path) -> std::io::Result<TemporaryFile> {
+        use flate2::read::GzDecoder;
+        use std::fs::File;
+        use std::io;
+        use std::process;
+
+        let mut temporary_path = std::env::temp_dir();
+        temporary_path.push(format!(
+            "tce-{}-{}-{}",
+            process::id(),
+            TEMPORARY_FILES.fetch_add(1, Ordering::SeqCst),
+            path.file_stem().unwrap().to_string_lossy()
+        ));
+        let mut decoder = GzDecoder::new(File::open(path)?);
+        io::copy(&mut decoder, &mut File::create(&temporary_path)?)?;
+        Ok(TemporaryFile(temporary_path))
+    }
+
+    /// Decompresses a laz file into the system's temporary directory, as las.
+    fn laszip(path: &Path) -> std::io::Result<TemporaryFile> {
+        use std::process;
+
+        let mut temporary_path = std::env::temp_dir();
+        temporary_path.push(format!(
+            "tce-{}-{}-{}.las",
+            process::id(),
+            TEMPORARY_FILES.fetch_add(1, Ordering::SeqCst),
+            path.file_stem().unwrap().to_string_lossy()
+        ));
+        let temporary_file = TemporaryFile(temporary_path);
+        laz::decompress(path, &temporary_file.0)?;
+        Ok(temporary_file)
+    }
+}
+
+impl Drop for TemporaryFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+impl Orientation {
+    /// Returns the pixel of the image file that pixel coordinates in the project's image fall in.
+    ///
+    /// The pixel is picked before it's mirrored, since mirroring the coordinates would put the
+    /// first row or column, e.g. `u == 0.`, at `width`, just outside of the image file.
+    fn pixel(&self, u: f64, v: f64, width: i32, height: i32) -> (i32, i32) {
+        let (x, y) = (u.floor() as i32, v.floor() as i32);
+        let (x, y, width, height) = match self.rotation {
+            Rotation::R0 => (x, y, width, height),
+            Rotation::R90 => (height - 1 - y, x, height, width),
+            Rotation::R180 => (width - 1 - x, height - 1 - y, width, height),
+            Rotation::R270 => (y, width - 1 - x, height, width),
+        };
+        (
+            if self.flip_horizontal { width - 1 - x } else { x },
+            if self.flip_vertical { height - 1 - y } else { y },
+        )
+    }
+}
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}°", self.rotation)?;
+        if self.flip_horizontal {
+            write!(f, ", flipped horizontally")?;
+        }
+        if self.flip_vertical {
+            write!(f, ", flipped vertically")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Frame {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Frame, String> {
+        match s {
+            "socs" => Ok(Frame::Socs),
+            "prcs" => Ok(Frame::Prcs),
+            "glcs" => Ok(Frame::Glcs),
+            _ => Err(format!("Invalid frame (must be socs, prcs, or glcs): {}", s)),
+        }
+    }
+}
+
+impl std::str::FromStr for Offset {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Offset, String> {
+        match s {
+            "pop" => Ok(Offset::Pop),
+            "auto" => Ok(Offset::Auto),
+            _ => {
+                let values = s.split(',')
+                    .map(|value| value.trim().parse::<f64>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| format!("Invalid offset value in {}: {}", s, err))?;
+                if values.len() == 3 {
+                    Ok(Offset::Values(values[0], values[1], values[2]))
+                } else {
+                    Err(format!(
+                        "Invalid offset (must be pop, auto, or three comma-separated values): {}",
+                        s
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for TimeWindow {
+    type Err = String;
+    fn from_str(s: &str) -> Result<TimeWindow, String> {
+        let values = s.split(',')
+            .map(|value| value.trim().parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| format!("Invalid time window value in {}: {}", s, err))?;
+        if values.len() != 2 {
+            return Err(format!(
+                "Invalid time window (must be two comma-separated values): {}",
+                s
+            ));
+        }
+        if values[0] > values[1] {
+            return Err(format!("Invalid time window (start is after end): {}", s));
+        }
+        Ok(TimeWindow {
+            start: values[0],
+            end: values[1],
+        })
+    }
+}
+
+impl Flag {
+    /// Marks a point.
+    fn apply(&self, point: &mut las::Point) {
+        match *self {
+            Flag::Withheld => point.is_withheld = true,
+            Flag::Synthetic => point.is_synthetic = true,
+            Flag::Classification(classification) => point.classification = classification,
+        }
+    }
+}
+
+impl std::str::FromStr for Flag {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Flag, String> {
+        match s {
+            "withheld" => Ok(Flag::Withheld),
+            "synthetic" => Ok(Flag::Synthetic),
+            _ => {
+                let code = s.parse::<u8>().map_err(|_| {
+                    format!(
+                        "Invalid flag (must be withheld, synthetic, or a classification): {}",
+                        s
+                    )
+                })?;
+                Classification::new(code)
+                    .map(Flag::Classification)
+                    .map_err(|err| format!("Invalid classification {}: {}", code, err))
+            }
+        }
+    }
+}
+
+impl NodataValue {
+    /// Replaces a NaN temperature with the sentinel value, if there is one.
+    fn apply(&self, temperature: f64) -> f64 {
+        match *self {
+            NodataValue::Value(value) if temperature.is_nan() => value,
+            _ => temperature,
+        }
+    }
+}
+
+impl std::str::FromStr for NodataValue {
+    type Err = String;
+    fn from_str(s: &str) -> Result<NodataValue, String> {
+        match s {
+            "nan" => Ok(NodataValue::Nan),
+            "omit-field" => Ok(NodataValue::OmitField),
+            _ => s.parse::<f64>().map(NodataValue::Value).map_err(|_| {
+                format!(
+                    "Invalid nodata value (must be nan, omit-field, or a number): {}",
+                    s
+                )
+            }),
+        }
+    }
+}
+
+impl std::str::FromStr for OutputLayout {
+    type Err = String;
+    fn from_str(s: &str) -> Result<OutputLayout, String> {
+        match s {
+            "flat" => Ok(OutputLayout::Flat),
+            "per-scanpos" => Ok(OutputLayout::PerScanPosition),
+            _ => Err(format!("Invalid output layout (must be flat or per-scanpos): {}", s)),
+        }
+    }
+}
+
+impl Default for Rotation {
+    fn default() -> Rotation {
+        Rotation::R0
+    }
+}
+
+impl std::str::FromStr for Rotation {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Rotation, String> {
+        match s {
+            "0" => Ok(Rotation::R0),
+            "90" => Ok(Rotation::R90),
+            "180" => Ok(Rotation::R180),
+            "270" => Ok(Rotation::R270),
+            _ => Err(format!("Invalid rotation (must be 0, 90, 180, or 270): {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Rotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let degrees = match *self {
+            Rotation::R0 => 0,
+            Rotation::R90 => 90,
+            Rotation::R180 => 180,
+            Rotation::R270 => 270,
+        };
+        write!(f, "{}", degrees)
+    }
+}
+
+impl Atmosphere {
+    /// Returns the transmission of the atmosphere over the given distance, in meters.
+    ///
+    /// This is the two-band water vapor model used by most thermal camera vendors, with the
+    /// standard coefficients.
+    fn transmission(&self, distance: f64) -> f64 {
+        const X: f64 = 1.9;
+        const ALPHA1: f64 = 0.006569;
+        const ALPHA2: f64 = 0.01262;
+        const BETA1: f64 = -0.002276;
+        const BETA2: f64 = -0.00667;
+
+        let t = self.temperature - 273.15;
+        let h2o = self.relative_humidity *
+            (1.5587 + 0.06939 * t - 0.00027816 * t.powi(2) + 0.00000068455 * t.powi(3)).exp();
+        let sqrt_distance = distance.sqrt();
+        let transmission = X * (-sqrt_distance * (ALPHA1 + BETA1 * h2o.sqrt())).exp() +
+            (1. - X) * (-sqrt_distance * (ALPHA2 + BETA2 * h2o.sqrt())).exp();
+        transmission.min(1.)
     }
 }
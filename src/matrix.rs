@@ -0,0 +1,52 @@
+use nalgebra::{Matrix4, Projective3};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+/// Parses sixteen comma- or whitespace-separated values, in row-major order, into a transform.
+pub fn parse(s: &str) -> Result<Projective3<f64>, String> {
+    let values = s.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<f64>().map_err(|err| format!("{}: {}", s, err)))
+        .collect::<Result<Vec<_>, _>>()?;
+    if values.len() != 16 {
+        return Err(format!("expected 16 matrix values, got {}", values.len()));
+    }
+    Ok(Projective3::from_matrix_unchecked(
+        Matrix4::from_row_slice(&values),
+    ))
+}
+
+/// Reads a file of per-scan-position matrices.
+///
+/// Each scan position is its name on a line, followed by four lines of four whitespace-separated
+/// values. Blank lines and lines starting with `#` are ignored.
+pub fn read_named<P: AsRef<Path>>(path: P) -> io::Result<HashMap<String, Projective3<f64>>> {
+    let mut lines = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            lines.push(line.to_string());
+        }
+    }
+    if lines.len() % 5 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "expected a name and four rows of values for each matrix",
+        ));
+    }
+    lines
+        .chunks(5)
+        .map(|chunk| {
+            let matrix = parse(&chunk[1..].join(" ")).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}: {}", chunk[0], err),
+                )
+            })?;
+            Ok((chunk[0].clone(), matrix))
+        })
+        .collect()
+}
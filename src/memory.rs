@@ -0,0 +1,61 @@
+//! Divides the `--max-memory` budget between the parts of the pipeline that buffer data.
+
+/// The share of the budget for the chunks of points passed between the stages of the pipeline.
+const CHUNK_SHARE: f64 = 0.25;
+
+/// The share of the budget for the buffers of the output files.
+const BUFFER_SHARE: f64 = 0.05;
+
+/// The share of the budget for thermal images decoded into memory, which is everything else.
+const IMAGE_SHARE: f64 = 1. - CHUNK_SHARE - BUFFER_SHARE;
+
+/// An optional cap on the memory used for buffering.
+///
+/// Without a cap, every part of the pipeline uses its default sizes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MemoryBudget {
+    max_bytes: Option<u64>,
+}
+
+impl MemoryBudget {
+    /// Creates a budget of `megabytes`, or an unlimited one.
+    pub fn new(megabytes: Option<f64>) -> MemoryBudget {
+        MemoryBudget {
+            max_bytes: megabytes.map(|megabytes| (megabytes * 1024. * 1024.) as u64),
+        }
+    }
+
+    /// Returns true if there's a cap.
+    pub fn is_limited(&self) -> bool {
+        self.max_bytes.is_some()
+    }
+
+    /// Returns the number of items of `size` bytes to hold at once, which is `default` unless it
+    /// doesn't fit in the chunk share, but always at least one.
+    pub fn chunk_len(&self, size: usize, default: usize) -> usize {
+        self.share(CHUNK_SHARE)
+            .map(|bytes| (bytes / size.max(1) as u64) as usize)
+            .unwrap_or(default)
+            .min(default)
+            .max(1)
+    }
+
+    /// Returns the capacity of one of `count` output file buffers, which is `default` unless they
+    /// don't fit in the buffer share, but always at least one kilobyte.
+    pub fn buffer_len(&self, count: usize, default: usize) -> usize {
+        self.share(BUFFER_SHARE)
+            .map(|bytes| (bytes / count.max(1) as u64) as usize)
+            .unwrap_or(default)
+            .min(default)
+            .max(1024)
+    }
+
+    /// Returns the number of bytes of decoded images to keep in memory, or `None` for no limit.
+    pub fn image_bytes(&self) -> Option<u64> {
+        self.share(IMAGE_SHARE)
+    }
+
+    fn share(&self, share: f64) -> Option<u64> {
+        self.max_bytes.map(|max_bytes| (max_bytes as f64 * share) as u64)
+    }
+}
@@ -0,0 +1,115 @@
+use geotiff::GeoTiff;
+use std::collections::HashMap;
+
+const NODATA: f64 = -9999.;
+
+/// How the temperatures that fall into one orthomosaic cell are combined.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Statistic {
+    Mean,
+    Max,
+}
+
+/// Grids points into square cells in the XY plane.
+#[derive(Clone, Debug)]
+pub struct Ortho {
+    resolution: f64,
+    cells: HashMap<(i64, i64), Cell>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Cell {
+    count: usize,
+    max: f64,
+    sum: f64,
+}
+
+impl Ortho {
+    /// Creates a new, empty orthomosaic with cells that are `resolution` units on a side.
+    pub fn new(resolution: f64) -> Ortho {
+        Ortho {
+            resolution: resolution,
+            cells: HashMap::new(),
+        }
+    }
+
+    /// Adds a temperature at a location.
+    pub fn add(&mut self, x: f64, y: f64, temperature: f64) {
+        let key = (
+            (x / self.resolution).floor() as i64,
+            (y / self.resolution).floor() as i64,
+        );
+        let cell = self.cells.entry(key).or_insert(Cell {
+            count: 0,
+            max: temperature,
+            sum: 0.,
+        });
+        cell.count += 1;
+        cell.max = cell.max.max(temperature);
+        cell.sum += temperature;
+    }
+
+    /// Adds all of the other orthomosaic's cells to this one.
+    ///
+    /// Both orthomosaics must have the same resolution.
+    pub fn merge(&mut self, other: &Ortho) {
+        assert_eq!(self.resolution, other.resolution);
+        for (key, other) in &other.cells {
+            let cell = self.cells.entry(*key).or_insert(Cell {
+                count: 0,
+                max: other.max,
+                sum: 0.,
+            });
+            cell.count += other.count;
+            cell.max = cell.max.max(other.max);
+            cell.sum += other.sum;
+        }
+    }
+
+    /// Returns true if no points have been added.
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Converts this orthomosaic to a raster that covers all of its cells.
+    ///
+    /// Cells without any points are nodata.
+    pub fn to_geotiff(&self, statistic: Statistic) -> GeoTiff {
+        let min_x = self.cells.keys().map(|key| key.0).min().unwrap_or(0);
+        let max_x = self.cells.keys().map(|key| key.0).max().unwrap_or(-1);
+        let min_y = self.cells.keys().map(|key| key.1).min().unwrap_or(0);
+        let max_y = self.cells.keys().map(|key| key.1).max().unwrap_or(-1);
+        let width = (max_x - min_x + 1) as usize;
+        let height = (max_y - min_y + 1) as usize;
+        let mut data = vec![NODATA; width * height];
+        for (&(x, y), cell) in &self.cells {
+            let index = (max_y - y) as usize * width + (x - min_x) as usize;
+            data[index] = match statistic {
+                Statistic::Mean => cell.sum / cell.count as f64,
+                Statistic::Max => cell.max,
+            };
+        }
+        GeoTiff::new(
+            width,
+            height,
+            (
+                min_x as f64 * self.resolution,
+                (max_y + 1) as f64 * self.resolution,
+            ),
+            (self.resolution, self.resolution),
+            Some(NODATA),
+            data,
+        )
+    }
+}
+
+impl ::std::str::FromStr for Statistic {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Statistic, String> {
+        match s {
+            "mean" => Ok(Statistic::Mean),
+            "max" => Ok(Statistic::Max),
+            _ => Err(format!("Invalid statistic (must be mean or max): {}", s)),
+        }
+    }
+}
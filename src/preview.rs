@@ -0,0 +1,62 @@
+use las::Color;
+use png;
+use raster;
+use std::f64::consts::PI;
+use std::io;
+use std::path::Path;
+
+/// A decimated, equirectangular (azimuth by elevation) rendering of a scan as seen from the
+/// scanner.
+///
+/// When several points fall into the same pixel, the closest one wins.
+#[derive(Clone, Debug)]
+pub struct Preview {
+    width: usize,
+    height: usize,
+    pixels: Vec<Option<(f64, Color)>>,
+}
+
+impl Preview {
+    /// Creates a new, black preview that is `width` pixels wide and half as tall.
+    pub fn new(width: usize) -> Preview {
+        let height = (width / 2).max(1);
+        Preview {
+            width: width,
+            height: height,
+            pixels: vec![None; width * height],
+        }
+    }
+
+    /// Adds a point, in the scanner's own coordinate system.
+    pub fn add(&mut self, x: f64, y: f64, z: f64, color: Color) {
+        let range = (x.powi(2) + y.powi(2) + z.powi(2)).sqrt();
+        if range == 0. {
+            return;
+        }
+        let azimuth = y.atan2(x);
+        let elevation = (z / range).asin();
+        let column = ((PI - azimuth) / (2. * PI) * self.width as f64) as usize;
+        let row = ((PI / 2. - elevation) / PI * self.height as f64) as usize;
+        let index = row.min(self.height - 1) * self.width + column.min(self.width - 1);
+        match self.pixels[index] {
+            Some((closest, _)) if closest <= range => {}
+            _ => self.pixels[index] = Some((range, color)),
+        }
+    }
+
+    /// Writes this preview as an 8-bit RGB png.
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut data = Vec::with_capacity(self.pixels.len() * 3);
+        for pixel in &self.pixels {
+            match *pixel {
+                Some((_, color)) => {
+                    data.push((color.red >> 8) as u8);
+                    data.push((color.green >> 8) as u8);
+                    data.push((color.blue >> 8) as u8);
+                }
+                None => data.extend_from_slice(&[0, 0, 0]),
+            }
+        }
+        raster::write_png(path, self.width, self.height, png::ColorType::RGB, &data)
+    }
+}
@@ -0,0 +1,52 @@
+//! Finds the RiSCAN Pro project file for the `PROJECT` argument.
+
+use glob::{self, Pattern};
+use riscan_pro::Project;
+use std::path::{Path, PathBuf};
+
+/// Opens a project from a `project.rsp` file, a `.RiSCAN` directory, or any directory with a
+/// single `project.rsp` somewhere inside it.
+///
+/// Panics if the directory has no project, or more than one.
+pub fn open(path: &str) -> Project {
+    let rsp_path = find(Path::new(path));
+    Project::from_path(&rsp_path).expect(&format!(
+        "Could not open RiSCAN Pro project {}",
+        rsp_path.display()
+    ))
+}
+
+/// Returns the path to the project file, or the path itself if `Project::from_path` can already
+/// find the project from it, i.e. if it's a file or inside a `.RiSCAN` directory.
+fn find(path: &Path) -> PathBuf {
+    let is_riscan = path.iter()
+        .any(|component| Path::new(component).extension().map_or(false, |e| e == "RiSCAN"));
+    if !path.is_dir() || is_riscan {
+        return path.to_path_buf();
+    }
+    if path.join("project.rsp").is_file() {
+        return path.join("project.rsp");
+    }
+    let pattern = format!("{}/**/project.rsp", Pattern::escape(&path.to_string_lossy()));
+    let mut rsp_paths = glob::glob(&pattern)
+        .expect(&format!("Invalid project search pattern: {}", pattern))
+        .filter_map(|rsp_path| rsp_path.ok())
+        .filter(|rsp_path| rsp_path.is_file())
+        .collect::<Vec<_>>();
+    rsp_paths.sort();
+    match rsp_paths.len() {
+        0 => panic!("No RiSCAN Pro project (project.rsp) in {}", path.display()),
+        1 => rsp_paths.pop().unwrap(),
+        _ => {
+            panic!(
+                "More than one RiSCAN Pro project in {}, provide one of these instead:\n{}",
+                path.display(),
+                rsp_paths
+                    .iter()
+                    .map(|rsp_path| format!("  - {}", rsp_path.display()))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )
+        }
+    }
+}
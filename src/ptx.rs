@@ -0,0 +1,124 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Lines};
+use std::path::Path;
+
+/// A point from a PTX file.
+#[derive(Clone, Copy, Debug)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    /// The intensity, from zero to one.
+    pub intensity: f64,
+}
+
+/// Reads the points of all of the scans in a PTX file.
+///
+/// Each point is transformed by its scan's header matrix. Missing points, which PTX writes as all
+/// zeros to keep the grid regular, are skipped.
+pub struct Reader<R: BufRead> {
+    lines: Lines<R>,
+    matrix: [[f64; 4]; 4],
+    remaining: usize,
+}
+
+impl Reader<BufReader<File>> {
+    /// Opens a PTX file.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Reader<BufReader<File>>> {
+        Ok(Reader {
+            lines: BufReader::new(File::open(path)?).lines(),
+            matrix: [[0.; 4]; 4],
+            remaining: 0,
+        })
+    }
+}
+
+impl<R: BufRead> Reader<R> {
+    fn next_values(&mut self) -> Option<io::Result<Vec<f64>>> {
+        loop {
+            let line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(err)) => return Some(Err(err)),
+                None => return None,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            return Some(
+                line.split_whitespace()
+                    .map(|s| {
+                        s.parse::<f64>().map_err(|err| {
+                            io::Error::new(io::ErrorKind::InvalidData, err)
+                        })
+                    })
+                    .collect(),
+            );
+        }
+    }
+
+    /// Reads a scan header: the grid size, the scanner position and axes, and the matrix.
+    fn read_header(&mut self, columns: f64) -> io::Result<()> {
+        let rows = self.expect_values(1)?[0];
+        for _ in 0..4 {
+            self.expect_values(3)?;
+        }
+        for i in 0..4 {
+            let values = self.expect_values(4)?;
+            for j in 0..4 {
+                self.matrix[i][j] = values[j];
+            }
+        }
+        self.remaining = (columns * rows) as usize;
+        Ok(())
+    }
+
+    fn expect_values(&mut self, len: usize) -> io::Result<Vec<f64>> {
+        match self.next_values() {
+            Some(Ok(ref values)) if values.len() >= len => Ok(values.clone()),
+            Some(Err(err)) => Err(err),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "truncated PTX header",
+            )),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = io::Result<Point>;
+
+    fn next(&mut self) -> Option<io::Result<Point>> {
+        loop {
+            let values = match self.next_values() {
+                Some(Ok(values)) => values,
+                Some(Err(err)) => return Some(Err(err)),
+                None => return None,
+            };
+            if self.remaining == 0 {
+                if let Err(err) = self.read_header(values[0]) {
+                    return Some(Err(err));
+                }
+                continue;
+            }
+            self.remaining -= 1;
+            if values.len() < 4 {
+                return Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "PTX points need at least x, y, z, and intensity",
+                )));
+            }
+            if values[0] == 0. && values[1] == 0. && values[2] == 0. {
+                continue;
+            }
+            // PTX matrices are written for row vectors, so the translation is the last row.
+            let m = &self.matrix;
+            let (x, y, z) = (values[0], values[1], values[2]);
+            return Some(Ok(Point {
+                x: x * m[0][0] + y * m[1][0] + z * m[2][0] + m[3][0],
+                y: x * m[0][1] + y * m[1][1] + z * m[2][1] + m[3][1],
+                z: x * m[0][2] + y * m[1][2] + z * m[2][2] + m[3][2],
+                intensity: values[3],
+            }));
+        }
+    }
+}
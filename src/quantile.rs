@@ -0,0 +1,178 @@
+//! Streaming quantile estimates, which need neither a second pass over the points nor the values
+//! in memory.
+
+use std::cmp::Ordering;
+
+/// The P² estimate of one quantile (Jain and Chlamtac, 1985), from five markers whose heights are
+/// adjusted as values are added.
+#[derive(Clone, Debug)]
+pub struct Quantile {
+    p: f64,
+    count: usize,
+    /// The marker heights, i.e. the minimum, the p/2, p, and (1+p)/2 quantiles, and the maximum.
+    heights: [f64; 5],
+    /// The actual marker positions, counting from one.
+    positions: [f64; 5],
+    /// The desired marker positions.
+    desired: [f64; 5],
+    /// How far each desired marker position moves with each value.
+    increments: [f64; 5],
+}
+
+impl Quantile {
+    /// Creates an estimator of the p-quantile, e.g. 0.98 for the 98th percentile.
+    pub fn new(p: f64) -> Quantile {
+        Quantile {
+            p: p,
+            count: 0,
+            heights: [0.; 5],
+            positions: [1., 2., 3., 4., 5.],
+            desired: [1., 1. + 2. * p, 1. + 4. * p, 3. + 2. * p, 5.],
+            increments: [0., p / 2., p, (1. + p) / 2., 1.],
+        }
+    }
+
+    /// Adds a value, ignoring NaN.
+    pub fn add(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        if self.count < 5 {
+            self.heights[self.count] = value;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights.sort_by(compare);
+            }
+            return;
+        }
+        self.count += 1;
+        let cell = if value < self.heights[0] {
+            self.heights[0] = value;
+            0
+        } else if value >= self.heights[4] {
+            self.heights[4] = value;
+            3
+        } else {
+            (0..4).find(|&i| value < self.heights[i + 1]).unwrap()
+        };
+        for position in &mut self.positions[cell + 1..] {
+            *position += 1.;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(self.increments.iter()) {
+            *desired += increment;
+        }
+        for i in 1..4 {
+            let offset = self.desired[i] - self.positions[i];
+            if offset >= 1. && self.positions[i + 1] - self.positions[i] > 1. ||
+                offset <= -1. && self.positions[i - 1] - self.positions[i] < -1.
+            {
+                let direction = offset.signum();
+                let height = self.parabolic(i, direction);
+                self.heights[i] = if self.heights[i - 1] < height && height < self.heights[i + 1] {
+                    height
+                } else {
+                    self.linear(i, direction)
+                };
+                self.positions[i] += direction;
+            }
+        }
+    }
+
+    /// Returns the estimate, or `None` if no values were added.
+    ///
+    /// With fewer than five values, this is the nearest of the sorted values.
+    pub fn estimate(&self) -> Option<f64> {
+        if self.count == 0 {
+            None
+        } else if self.count < 5 {
+            let mut values = self.heights[..self.count].to_vec();
+            values.sort_by(compare);
+            Some(values[((self.count - 1) as f64 * self.p).round() as usize])
+        } else {
+            Some(self.heights[2])
+        }
+    }
+
+    /// Moves a marker's height along the parabola through it and its neighbors.
+    fn parabolic(&self, i: usize, direction: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        q[i] +
+            direction / (n[i + 1] - n[i - 1]) *
+                ((n[i] - n[i - 1] + direction) * (q[i + 1] - q[i]) / (n[i + 1] - n[i]) +
+                     (n[i + 1] - n[i] - direction) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    /// Moves a marker's height along the line to the neighbor in `direction`.
+    fn linear(&self, i: usize, direction: f64) -> f64 {
+        let j = if direction > 0. { i + 1 } else { i - 1 };
+        self.heights[i] +
+            direction * (self.heights[j] - self.heights[i]) /
+                (self.positions[j] - self.positions[i])
+    }
+}
+
+fn compare(a: &f64, b: &f64) -> Ordering {
+    a.partial_cmp(b).unwrap_or(Ordering::Equal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the p-quantile of sorted values, as the nearest rank.
+    fn reference(sorted: &[f64], p: f64) -> f64 {
+        sorted[((sorted.len() - 1) as f64 * p).round() as usize]
+    }
+
+    #[test]
+    fn empty() {
+        assert_eq!(None, Quantile::new(0.5).estimate());
+    }
+
+    #[test]
+    fn fewer_than_five() {
+        let mut quantile = Quantile::new(0.5);
+        for &value in &[3., 1., 2.] {
+            quantile.add(value);
+        }
+        assert_eq!(Some(2.), quantile.estimate());
+    }
+
+    #[test]
+    fn paper() {
+        // The example from Jain and Chlamtac (1985), table 1.
+        let values = [
+            0.02, 0.15, 0.74, 3.39, 0.83, 22.37, 10.15, 15.43, 38.62, 15.92, 34.60, 10.28, 1.47,
+            0.40, 0.05, 11.39, 0.27, 0.42, 0.09, 11.37,
+        ];
+        let mut quantile = Quantile::new(0.5);
+        for &value in &values {
+            quantile.add(value);
+        }
+        assert!((quantile.estimate().unwrap() - 4.44).abs() < 0.01);
+    }
+
+    #[test]
+    fn sorted_reference() {
+        // Every integer from 0 to 10000, in a scrambled order, with a NaN that's ignored.
+        let mut values = (0..10001).map(|i| (i * 7919 % 10001) as f64).collect::<Vec<_>>();
+        values.insert(5000, std::f64::NAN);
+        let mut sorted = values.iter().cloned().filter(|v| !v.is_nan()).collect::<Vec<_>>();
+        sorted.sort_by(compare);
+        for &p in &[0.02, 0.5, 0.98] {
+            let mut quantile = Quantile::new(p);
+            for &value in &values {
+                quantile.add(value);
+            }
+            let expected = reference(&sorted, p);
+            let estimate = quantile.estimate().unwrap();
+            assert!(
+                (estimate - expected).abs() < 50.,
+                "p {}: estimated {}, expected {}",
+                p,
+                estimate,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use png;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter};
+use std::path::Path;
+
+/// A simple two-dimensional grid of values, indexed by image pixel.
+#[derive(Clone, Debug)]
+pub struct Raster {
+    pub width: usize,
+    pub height: usize,
+    data: Vec<f64>,
+}
+
+impl Raster {
+    /// Reads a raster from an ascii grid, one row of whitespace-separated values per line.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<Raster> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut width = None;
+        let mut data = Vec::new();
+        let mut height = 0;
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let row = line.split_whitespace()
+                .map(|s| {
+                    s.parse::<f64>().map_err(|err| {
+                        io::Error::new(io::ErrorKind::InvalidData, err)
+                    })
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            if *width.get_or_insert(row.len()) != row.len() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("row {} has {} values, expected {}", height, row.len(), width.unwrap()),
+                ));
+            }
+            data.extend(row);
+            height += 1;
+        }
+        Ok(Raster {
+            width: width.unwrap_or(0),
+            height: height,
+            data: data,
+        })
+    }
+
+    /// Returns the value at the pixel, or `None` if the pixel is outside of the raster.
+    pub fn get(&self, x: i32, y: i32) -> Option<f64> {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            None
+        } else {
+            Some(self.data[y as usize * self.width + x as usize])
+        }
+    }
+}
+
+/// A set of pixels that should never be sampled, e.g. the dead pixels of a detector.
+#[derive(Clone, Debug, Default)]
+pub struct BadPixels {
+    pixels: HashSet<(i32, i32)>,
+}
+
+impl BadPixels {
+    /// Reads bad pixels from a text file with one whitespace-separated `column row` pair per line.
+    ///
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<BadPixels> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut pixels = HashSet::new();
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let values = line.split_whitespace()
+                .map(|s| {
+                    s.parse::<i32>().map_err(|err| {
+                        io::Error::new(io::ErrorKind::InvalidData, err)
+                    })
+                })
+                .collect::<io::Result<Vec<_>>>()?;
+            if values.len() != 2 {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("expected a column and a row, got: {}", line),
+                ));
+            }
+            pixels.insert((values[0], values[1]));
+        }
+        Ok(BadPixels { pixels: pixels })
+    }
+
+    /// Returns true if this pixel is bad.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        self.pixels.contains(&(x, y))
+    }
+}
+
+/// A per-pixel mask, e.g. covering the parts of an image that show the tripod or the sun.
+#[derive(Clone, Debug)]
+pub struct Mask {
+    width: usize,
+    height: usize,
+    data: Vec<bool>,
+}
+
+impl Mask {
+    /// Reads a mask from a png file.
+    ///
+    /// Pixels that are brighter than mid-gray (in their first channel) are masked; black pixels
+    /// are not.
+    pub fn from_png<P: AsRef<Path>>(path: P) -> io::Result<Mask> {
+        let decoder = png::Decoder::new(File::open(path)?);
+        let (info, mut reader) = decoder.read_info().map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidData, err)
+        })?;
+        let mut buf = vec![0; info.buffer_size()];
+        reader.next_frame(&mut buf).map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidData, err)
+        })?;
+        let samples = info.color_type.samples();
+        let width = info.width as usize;
+        let height = info.height as usize;
+        let mut data = Vec::with_capacity(width * height);
+        for row in buf.chunks(info.line_size).take(height) {
+            data.extend(row.chunks(samples).take(width).map(|pixel| pixel[0] >= 128));
+        }
+        Ok(Mask {
+            width: width,
+            height: height,
+            data: data,
+        })
+    }
+
+    /// Returns true if the pixel is masked.
+    ///
+    /// Pixels outside of the mask are never masked.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            false
+        } else {
+            self.data[y as usize * self.width + x as usize]
+        }
+    }
+}
+
+/// Writes an 8-bit png from row-major data.
+pub fn write_png<P: AsRef<Path>>(
+    path: P,
+    width: usize,
+    height: usize,
+    color_type: png::ColorType,
+    data: &[u8],
+) -> io::Result<()> {
+    use png::HasParameters;
+
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        width as u32,
+        height as u32,
+    );
+    encoder.set(color_type).set(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(data))
+        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+}
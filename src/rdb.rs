@@ -0,0 +1,247 @@
+//! A minimal binding to RIEGL's RDB 2 C library, for reading rdbx point clouds.
+//!
+//! Only the functions we need are declared, following `riegl/rdb.h`.
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+use std::path::Path;
+use std::ptr;
+
+/// How many points are read from the library at a time.
+const BUFFER_SIZE: usize = 100_000;
+
+/// The `RDB_STATUS` that every function returns, as defined in `riegl/rdb.h`. A failure's
+/// details are the context's last error.
+const RDB_FAILURE: u32 = 0;
+
+const RDB_DATA_TYPE_FLOAT32: u32 = 9;
+const RDB_DATA_TYPE_DOUBLE: u32 = 10;
+
+enum RdbContext {}
+enum RdbPointcloud {}
+enum RdbPointcloudOpenSettings {}
+enum RdbPointcloudQuerySelect {}
+
+#[link(name = "rdb")]
+extern "C" {
+    fn rdb_context_new(
+        context: *mut *mut RdbContext,
+        log_level: *const c_char,
+        log_path: *const c_char,
+    ) -> u32;
+    fn rdb_context_delete(context: *mut *mut RdbContext) -> u32;
+    fn rdb_context_get_last_error(
+        context: *mut RdbContext,
+        code: *mut i32,
+        text: *mut *const c_char,
+        details: *mut *const c_char,
+    ) -> u32;
+    fn rdb_pointcloud_new(context: *mut RdbContext, pointcloud: *mut *mut RdbPointcloud) -> u32;
+    fn rdb_pointcloud_delete(
+        context: *mut RdbContext,
+        pointcloud: *mut *mut RdbPointcloud,
+    ) -> u32;
+    fn rdb_pointcloud_open_settings_new(
+        context: *mut RdbContext,
+        settings: *mut *mut RdbPointcloudOpenSettings,
+    ) -> u32;
+    fn rdb_pointcloud_open_settings_delete(
+        context: *mut RdbContext,
+        settings: *mut *mut RdbPointcloudOpenSettings,
+    ) -> u32;
+    fn rdb_pointcloud_open(
+        context: *mut RdbContext,
+        pointcloud: *mut RdbPointcloud,
+        location: *const c_char,
+        settings: *const RdbPointcloudOpenSettings,
+    ) -> u32;
+    fn rdb_pointcloud_query_select_new(
+        context: *mut RdbContext,
+        pointcloud: *mut RdbPointcloud,
+        nodes: *const u32,
+        count: u32,
+        filter: *const c_char,
+        query: *mut *mut RdbPointcloudQuerySelect,
+    ) -> u32;
+    fn rdb_pointcloud_query_select_delete(
+        context: *mut RdbContext,
+        query: *mut *mut RdbPointcloudQuerySelect,
+    ) -> u32;
+    fn rdb_pointcloud_query_select_bind(
+        context: *mut RdbContext,
+        query: *mut RdbPointcloudQuerySelect,
+        attribute: *const c_char,
+        data_type: u32,
+        buffer: *mut c_void,
+        stride: i32,
+    ) -> u32;
+    fn rdb_pointcloud_query_select_next(
+        context: *mut RdbContext,
+        query: *mut RdbPointcloudQuerySelect,
+        count: u32,
+        processed: *mut u32,
+    ) -> u32;
+}
+
+/// A point from an rdbx file, in the scanner's own coordinate system.
+#[derive(Clone, Copy, Debug)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+    pub reflectance: f32,
+}
+
+/// Reads every point of an rdbx file.
+pub struct Reader {
+    context: *mut RdbContext,
+    pointcloud: *mut RdbPointcloud,
+    query: *mut RdbPointcloudQuerySelect,
+    xyz: Vec<[f64; 3]>,
+    reflectance: Vec<f32>,
+    len: usize,
+    index: usize,
+    done: bool,
+}
+
+impl Reader {
+    /// Opens an rdbx file and selects all of its points.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Reader, String> {
+        let location = CString::new(path.as_ref().to_string_lossy().into_owned())
+            .map_err(|err| err.to_string())?;
+        let empty = CString::new("").unwrap();
+        let mut reader = Reader {
+            context: ptr::null_mut(),
+            pointcloud: ptr::null_mut(),
+            query: ptr::null_mut(),
+            xyz: vec![[0.; 3]; BUFFER_SIZE],
+            reflectance: vec![0.; BUFFER_SIZE],
+            len: 0,
+            index: 0,
+            done: false,
+        };
+        unsafe {
+            let result = rdb_context_new(&mut reader.context, empty.as_ptr(), empty.as_ptr());
+            if result == RDB_FAILURE {
+                return Err("could not create an rdb context".to_string());
+            }
+            let result = rdb_pointcloud_new(reader.context, &mut reader.pointcloud);
+            reader.check(result)?;
+            let mut settings = ptr::null_mut();
+            let result = rdb_pointcloud_open_settings_new(reader.context, &mut settings);
+            reader.check(result)?;
+            let result = rdb_pointcloud_open(
+                reader.context,
+                reader.pointcloud,
+                location.as_ptr(),
+                settings,
+            );
+            rdb_pointcloud_open_settings_delete(reader.context, &mut settings);
+            reader.check(result)?;
+            let result = rdb_pointcloud_query_select_new(
+                reader.context,
+                reader.pointcloud,
+                ptr::null(),
+                0,
+                empty.as_ptr(),
+                &mut reader.query,
+            );
+            reader.check(result)?;
+            let xyz = CString::new("riegl.xyz").unwrap();
+            let result = rdb_pointcloud_query_select_bind(
+                reader.context,
+                reader.query,
+                xyz.as_ptr(),
+                RDB_DATA_TYPE_DOUBLE,
+                reader.xyz.as_mut_ptr() as *mut c_void,
+                24,
+            );
+            reader.check(result)?;
+            let reflectance = CString::new("riegl.reflectance").unwrap();
+            let result = rdb_pointcloud_query_select_bind(
+                reader.context,
+                reader.query,
+                reflectance.as_ptr(),
+                RDB_DATA_TYPE_FLOAT32,
+                reader.reflectance.as_mut_ptr() as *mut c_void,
+                4,
+            );
+            reader.check(result)?;
+        }
+        Ok(reader)
+    }
+
+    /// Turns an `RDB_FAILURE` result into the context's last error.
+    fn check(&self, result: u32) -> Result<(), String> {
+        if result != RDB_FAILURE {
+            return Ok(());
+        }
+        let mut code = 0;
+        let mut text = ptr::null();
+        let mut details = ptr::null();
+        unsafe {
+            rdb_context_get_last_error(self.context, &mut code, &mut text, &mut details);
+            if text.is_null() {
+                Err(format!("rdb error {}", code))
+            } else {
+                Err(CStr::from_ptr(text).to_string_lossy().into_owned())
+            }
+        }
+    }
+}
+
+impl Iterator for Reader {
+    type Item = Result<Point, String>;
+
+    fn next(&mut self) -> Option<Result<Point, String>> {
+        if self.index == self.len {
+            if self.done {
+                return None;
+            }
+            let mut processed = 0;
+            let result = unsafe {
+                rdb_pointcloud_query_select_next(
+                    self.context,
+                    self.query,
+                    BUFFER_SIZE as u32,
+                    &mut processed,
+                )
+            };
+            if let Err(err) = self.check(result) {
+                self.done = true;
+                return Some(Err(err));
+            }
+            self.len = processed as usize;
+            self.index = 0;
+            if self.len == 0 {
+                self.done = true;
+                return None;
+            }
+        }
+        let xyz = self.xyz[self.index];
+        let reflectance = self.reflectance[self.index];
+        self.index += 1;
+        Some(Ok(Point {
+            x: xyz[0],
+            y: xyz[1],
+            z: xyz[2],
+            reflectance: reflectance,
+        }))
+    }
+}
+
+impl Drop for Reader {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.query.is_null() {
+                rdb_pointcloud_query_select_delete(self.context, &mut self.query);
+            }
+            if !self.pointcloud.is_null() {
+                rdb_pointcloud_delete(self.context, &mut self.pointcloud);
+            }
+            if !self.context.is_null() {
+                rdb_context_delete(&mut self.context);
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use png;
+use raster;
+use riscan_pro::{CameraCalibration, Cmcs, Point};
+use std::io;
+use std::path::Path;
+
+/// The width of the rejection map, in pixels.
+const WIDTH: usize = 256;
+
+/// Counts why points were, or weren't, projected into an image, for `--rejections`.
+///
+/// The points are also mapped by the tangents of their angles from the camera's axis, over twice
+/// the camera calibration's angle extents, so the angle mask can be seen against the image bounds.
+#[derive(Clone, Debug)]
+pub struct Rejections {
+    pub accepted: u64,
+    pub behind_camera: u64,
+    pub angle_mask: u64,
+    pub bounds: u64,
+    height: usize,
+    horz: (f64, f64),
+    vert: (f64, f64),
+    /// Accepted, angle mask, and bounds counts per map pixel.
+    map: Vec<[u32; 3]>,
+}
+
+impl Rejections {
+    /// Creates empty rejections for a camera.
+    pub fn new(camera_calibration: &CameraCalibration) -> Rejections {
+        let margin = |min: f64, max: f64| (max - min) / 2.;
+        let horz_margin = margin(camera_calibration.tan_min_horz, camera_calibration.tan_max_horz);
+        let vert_margin = margin(camera_calibration.tan_min_vert, camera_calibration.tan_max_vert);
+        let horz = (
+            camera_calibration.tan_min_horz - horz_margin,
+            camera_calibration.tan_max_horz + horz_margin,
+        );
+        let vert = (
+            camera_calibration.tan_min_vert - vert_margin,
+            camera_calibration.tan_max_vert + vert_margin,
+        );
+        let height = ((WIDTH as f64 * (vert.1 - vert.0) / (horz.1 - horz.0)) as usize)
+            .max(1)
+            .min(4 * WIDTH);
+        Rejections {
+            accepted: 0,
+            behind_camera: 0,
+            angle_mask: 0,
+            bounds: 0,
+            height: height,
+            horz: horz,
+            vert: vert,
+            map: vec![[0; 3]; WIDTH * height],
+        }
+    }
+
+    /// Adds a point in the camera's coordinate system, given whether it was projected to a pixel.
+    ///
+    /// Points that weren't are attributed to the first check that rejects them, in the order that
+    /// `CameraCalibration::cmcs_to_ics` checks them: behind the camera, then outside the angle
+    /// mask, then outside of the image.
+    pub fn add(
+        &mut self,
+        cmcs: &Point<Cmcs>,
+        is_accepted: bool,
+        camera_calibration: &CameraCalibration,
+    ) {
+        if cmcs.is_behind_camera() {
+            self.behind_camera += 1;
+            return;
+        }
+        let (tan_horz, tan_vert) = (cmcs.tan_horz(), cmcs.tan_vert());
+        let reason = if is_accepted {
+            self.accepted += 1;
+            0
+        } else if tan_horz < camera_calibration.tan_min_horz ||
+                   tan_horz > camera_calibration.tan_max_horz ||
+                   tan_vert < camera_calibration.tan_min_vert ||
+                   tan_vert > camera_calibration.tan_max_vert
+        {
+            self.angle_mask += 1;
+            1
+        } else {
+            self.bounds += 1;
+            2
+        };
+        let column = (tan_horz - self.horz.0) / (self.horz.1 - self.horz.0) * WIDTH as f64;
+        let row = (tan_vert - self.vert.0) / (self.vert.1 - self.vert.0) * self.height as f64;
+        if column >= 0. && row >= 0. && (column as usize) < WIDTH && (row as usize) < self.height {
+            self.map[row as usize * WIDTH + column as usize][reason] += 1;
+        }
+    }
+
+    /// Writes the map as a png, with accepted points in green, angle mask rejections in red, and
+    /// bounds rejections in blue, each scaled to its busiest pixel.
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut max = [1; 3];
+        for counts in &self.map {
+            for (max, &count) in max.iter_mut().zip(counts.iter()) {
+                *max = (*max).max(count);
+            }
+        }
+        let scale = |count: u32, max: u32| {
+            if count == 0 {
+                0
+            } else {
+                (63. + 192. * f64::from(count) / f64::from(max)) as u8
+            }
+        };
+        let mut data = Vec::with_capacity(self.map.len() * 3);
+        for counts in &self.map {
+            data.push(scale(counts[1], max[1]));
+            data.push(scale(counts[0], max[0]));
+            data.push(scale(counts[2], max[2]));
+        }
+        raster::write_png(path, WIDTH, self.height, png::ColorType::RGB, &data)
+    }
+}
@@ -0,0 +1,214 @@
+//! A self-contained HTML report of a run, for `--report`.
+
+use std::fmt::Write as FmtWrite;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use summary::{Histogram, Summary};
+
+const BASE64: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// The size of the histogram charts, in pixels.
+const CHART_WIDTH: usize = 400;
+const CHART_HEIGHT: usize = 100;
+
+/// Writes the report, with the configuration (as printed at the start of the run), the statistics
+/// and temperature histogram of each scan position, the timing of each translation, and the
+/// preview images, if any.
+///
+/// Previews are embedded so the report can be shared on its own.
+pub fn write<P: AsRef<Path>>(path: P, configuration: &str, summary: &Summary) -> io::Result<()> {
+    let mut html = String::new();
+    html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<title>tce report</title>\n<style>\n");
+    html.push_str("body { font-family: sans-serif; margin: 2em; }\n");
+    html.push_str("table { border-collapse: collapse; }\n");
+    html.push_str("th, td { border: 1px solid #ccc; padding: 0.25em 0.5em; text-align: right; }\n");
+    html.push_str("th:first-child, td:first-child { text-align: left; }\n");
+    html.push_str("img { max-width: 400px; margin-right: 1em; }\n");
+    html.push_str("</style>\n</head>\n<body>\n<h1>tce report</h1>\n");
+    writeln!(html, "<pre>{}</pre>", escape(configuration)).unwrap();
+
+    html.push_str("<h2>Scan positions</h2>\n<table>\n");
+    html.push_str(
+        "<tr><th>Scan position</th><th>Translations</th><th>Read</th><th>Written</th>\
+         <th>Without thermal</th><th>Without baseline</th><th>Thermal coverage</th>\
+         <th>Seconds</th></tr>\n",
+    );
+    for scan_position in &summary.scan_positions {
+        let counts = &scan_position.counts;
+        writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+             <td>{:.1}</td></tr>",
+            escape(&scan_position.name),
+            scan_position.translations,
+            counts.read,
+            counts.written,
+            counts.no_thermal,
+            counts.no_baseline,
+            coverage(counts.read, counts.no_thermal),
+            scan_position.seconds
+        ).unwrap();
+    }
+    let total = &summary.total;
+    writeln!(
+        html,
+        "<tr><th>Total</th><th></th><th>{}</th><th>{}</th><th>{}</th><th>{}</th><th>{}</th>\
+         <th>{:.1}</th></tr>\n</table>",
+        total.read,
+        total.written,
+        total.no_thermal,
+        total.no_baseline,
+        coverage(total.read, total.no_thermal),
+        summary.seconds
+    ).unwrap();
+
+    if summary.scan_positions.iter().any(|scan_position| !scan_position.throughputs.is_empty()) {
+        html.push_str("<h2>Translations</h2>\n<table>\n");
+        html.push_str(
+            "<tr><th>Infile</th><th>Scan position</th><th>Seconds</th><th>Read/s</th>\
+             <th>Written/s</th></tr>\n",
+        );
+        for scan_position in &summary.scan_positions {
+            for throughput in &scan_position.throughputs {
+                writeln!(
+                    html,
+                    "<tr><td>{}</td><td>{}</td><td>{:.1}</td><td>{:.0}</td><td>{:.0}</td></tr>",
+                    escape(&throughput.infile),
+                    escape(&scan_position.name),
+                    throughput.seconds,
+                    throughput.read_per_second,
+                    throughput.written_per_second
+                ).unwrap();
+            }
+        }
+        html.push_str("</table>\n");
+    }
+
+    if !summary.failures.is_empty() {
+        html.push_str("<h2>Failures</h2>\n<ul>\n");
+        for failure in &summary.failures {
+            writeln!(
+                html,
+                "<li>{} ({}): {}</li>",
+                escape(&failure.infile),
+                escape(&failure.scan_position),
+                escape(&failure.message)
+            ).unwrap();
+        }
+        html.push_str("</ul>\n");
+    }
+
+    if !summary.unlinked_images.is_empty() {
+        html.push_str("<h2>Unlinked images</h2>\n<ul>\n");
+        for path in &summary.unlinked_images {
+            writeln!(html, "<li>{}</li>", escape(path)).unwrap();
+        }
+        html.push_str("</ul>\n");
+    }
+
+    if !summary.unreadable_images.is_empty() {
+        html.push_str("<h2>Unreadable images</h2>\n<ul>\n");
+        for path in &summary.unreadable_images {
+            writeln!(html, "<li>{}</li>", escape(path)).unwrap();
+        }
+        html.push_str("</ul>\n");
+    }
+
+    if let Some(first) = summary.scan_positions.first() {
+        let mut histogram = Histogram::new(first.histogram.min, first.histogram.max);
+        for scan_position in &summary.scan_positions {
+            histogram.merge(&scan_position.histogram);
+        }
+        html.push_str("<h2>Temperatures</h2>\n<h3>All scan positions</h3>\n");
+        html.push_str(&chart(&histogram));
+    }
+    for scan_position in &summary.scan_positions {
+        writeln!(html, "<h3>{}</h3>", escape(&scan_position.name)).unwrap();
+        html.push_str(&chart(&scan_position.histogram));
+        for preview in &scan_position.previews {
+            match data_uri(preview) {
+                Ok(uri) => {
+                    writeln!(html, "<img src=\"{}\" alt=\"{}\">", uri, escape(preview)).unwrap()
+                }
+                Err(err) => warn!("Could not embed preview {} in the report: {}", preview, err),
+            }
+        }
+    }
+    html.push_str("</body>\n</html>\n");
+    File::create(path)?.write_all(html.as_bytes())
+}
+
+/// Returns the percentage of read points that had thermal data.
+fn coverage(read: u64, no_thermal: u64) -> String {
+    if read == 0 {
+        "n/a".to_string()
+    } else {
+        format!("{:.1}%", 100. * (read - no_thermal) as f64 / read as f64)
+    }
+}
+
+/// Draws a histogram as an inline svg bar chart, with its domain and out-of-domain counts below.
+fn chart(histogram: &Histogram) -> String {
+    let max = histogram.bins.iter().cloned().max().unwrap_or(0).max(1);
+    let bar_width = CHART_WIDTH as f64 / histogram.bins.len() as f64;
+    let mut svg = format!(
+        "<svg width=\"{}\" height=\"{}\" style=\"background: #f4f4f4\">",
+        CHART_WIDTH,
+        CHART_HEIGHT
+    );
+    for (i, &count) in histogram.bins.iter().enumerate() {
+        let height = CHART_HEIGHT as f64 * count as f64 / max as f64;
+        write!(
+            svg,
+            "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"#c33\">\
+             <title>{}</title></rect>",
+            i as f64 * bar_width,
+            CHART_HEIGHT as f64 - height,
+            bar_width - 1.,
+            height,
+            count
+        ).unwrap();
+    }
+    svg.push_str("</svg>\n");
+    write!(
+        svg,
+        "<p>{} to {} °C, {} below and {} above</p>\n",
+        histogram.min,
+        histogram.max,
+        histogram.below,
+        histogram.above
+    ).unwrap();
+    svg
+}
+
+/// Reads a png into a base64 data uri.
+fn data_uri(path: &str) -> io::Result<String> {
+    let mut bytes = Vec::new();
+    File::open(path)?.read_to_end(&mut bytes)?;
+    let mut uri = "data:image/png;base64,".to_string();
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).cloned().unwrap_or(0),
+            chunk.get(2).cloned().unwrap_or(0),
+        ];
+        let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                uri.push(BASE64[n >> (18 - 6 * i) & 63] as char);
+            } else {
+                uri.push('=');
+            }
+        }
+    }
+    Ok(uri)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
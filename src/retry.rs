@@ -0,0 +1,49 @@
+use std::fmt::Debug;
+use std::thread;
+use std::time::Duration;
+
+/// How often to retry operations that can fail transiently, e.g. opening files on network
+/// storage.
+#[derive(Clone, Copy, Debug)]
+pub struct Retry {
+    /// The number of retries after the first attempt.
+    pub retries: u32,
+    /// The wait before the first retry, which doubles for each retry after that.
+    pub delay: Duration,
+}
+
+impl Retry {
+    /// Calls `f` until it succeeds or we run out of retries, returning the last error.
+    ///
+    /// Each failure that will be retried is logged as a warning, using `description` (e.g. "open
+    /// foo.rxp").
+    pub fn run<T, E, F>(&self, description: &str, mut f: F) -> Result<T, E>
+    where
+        E: Debug,
+        F: FnMut() -> Result<T, E>,
+    {
+        let mut delay = self.delay;
+        let mut retry = 0;
+        loop {
+            match f() {
+                Ok(value) => return Ok(value),
+                Err(err) => {
+                    if retry >= self.retries {
+                        return Err(err);
+                    }
+                    retry += 1;
+                    warn!(
+                        "could not {}, retrying in {:?} ({} of {}): {:?}",
+                        description,
+                        delay,
+                        retry,
+                        self.retries,
+                        err
+                    );
+                    thread::sleep(delay);
+                    delay = delay * 2;
+                }
+            }
+        }
+    }
+}
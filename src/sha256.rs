@@ -0,0 +1,45 @@
+//! SHA-256 checksums of output files and of `--state-file` fingerprints.
+
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Returns the hex-encoded SHA-256 of a file's contents.
+pub fn file<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    digest(File::open(path)?)
+}
+
+/// Returns the hex-encoded SHA-256 of a string.
+pub fn string(s: &str) -> String {
+    digest(s.as_bytes()).unwrap()
+}
+
+fn digest<R: Read>(mut read: R) -> io::Result<String> {
+    let mut sha256 = Sha256::default();
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        let n = read.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        sha256.input(&buffer[..n]);
+    }
+    Ok(sha256
+        .result()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Writes a `sha256sum`-style sidecar next to a file, e.g. `SP01.las.sha256`, returning its path.
+pub fn write_sidecar<P: AsRef<Path>>(path: P, checksum: &str) -> io::Result<PathBuf> {
+    let path = path.as_ref();
+    let file_name = path.file_name().map(|file_name| file_name.to_string_lossy().into_owned());
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".sha256");
+    let sidecar = PathBuf::from(sidecar);
+    let mut file = File::create(&sidecar)?;
+    writeln!(file, "{}  {}", checksum, file_name.unwrap_or_default())?;
+    Ok(sidecar)
+}
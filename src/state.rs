@@ -0,0 +1,53 @@
+//! Remembers which translations have been colorized, for `--state-file`.
+
+use serde_json;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// The fingerprint of each colorized translation's inputs, by input file.
+#[derive(Debug)]
+pub struct State {
+    path: PathBuf,
+    fingerprints: BTreeMap<String, String>,
+}
+
+impl State {
+    /// Reads the state file, or starts an empty state if it doesn't exist yet.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> io::Result<State> {
+        let path = path.as_ref().to_path_buf();
+        let fingerprints = if path.exists() {
+            serde_json::from_reader(File::open(&path)?)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?
+        } else {
+            BTreeMap::new()
+        };
+        Ok(State {
+            path: path,
+            fingerprints: fingerprints,
+        })
+    }
+
+    /// Returns true if the input file was colorized with inputs that had this fingerprint.
+    pub fn is_current(&self, infile: &Path, fingerprint: &str) -> bool {
+        self.fingerprints
+            .get(infile.to_string_lossy().as_ref())
+            .map(|current| current == fingerprint)
+            .unwrap_or(false)
+    }
+
+    /// Records that an input file was colorized, and saves the state file.
+    ///
+    /// The state is written to a temporary file first, so an interrupted run can't leave a
+    /// truncated state file behind.
+    pub fn record(&mut self, infile: &Path, fingerprint: String) -> io::Result<()> {
+        self.fingerprints
+            .insert(infile.to_string_lossy().into_owned(), fingerprint);
+        let mut temporary_path = self.path.as_os_str().to_owned();
+        temporary_path.push(".tmp");
+        serde_json::to_writer_pretty(File::create(&temporary_path)?, &self.fingerprints)
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        fs::rename(temporary_path, &self.path)
+    }
+}
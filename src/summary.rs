@@ -0,0 +1,307 @@
+use serde_json;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+/// Point counts for one or more colorized files.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Counts {
+    pub read: u64,
+    pub written: u64,
+    /// Points that were skipped because no image or thermal orthomosaic covered them.
+    pub no_thermal: u64,
+    /// Points without thermal that were behind the camera of every image.
+    pub behind_camera: u64,
+    /// Points without thermal that were in front of a camera, but outside of every image.
+    pub outside_images: u64,
+    /// Points without thermal that projected into images, but only onto masked, bad, or
+    /// unreadable pixels, or pixels outside the valid temperatures.
+    pub invalid_pixels: u64,
+    /// Points without thermal because the translation had no images.
+    pub no_images: u64,
+    /// Points without thermal because fewer than `--min-images` images saw them.
+    pub too_few_images: u64,
+    /// Points without thermal because their temperatures differed by more than
+    /// `--max-temperature-spread`.
+    pub disagreement: u64,
+    /// Points that were skipped because no `--delta-dir` image covered them.
+    pub no_baseline: u64,
+    /// Points that were never colorized because of `--skip-points` or `--time-window`, which
+    /// aren't counted as read.
+    pub filtered: u64,
+}
+
+/// Why a point has no temperature, from the closest it came to being colorized.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoThermal {
+    NoImages,
+    BehindCamera,
+    OutsideImages,
+    InvalidPixels,
+    TooFewImages,
+    Disagreement,
+}
+
+/// The number of bins in a temperature histogram.
+const HISTOGRAM_BINS: usize = 20;
+
+/// A histogram of written temperatures over the color scale's domain.
+#[derive(Clone, Debug, Serialize)]
+pub struct Histogram {
+    pub min: f64,
+    pub max: f64,
+    pub bins: Vec<u64>,
+    /// Temperatures below `min`.
+    pub below: u64,
+    /// Temperatures above `max`.
+    pub above: u64,
+}
+
+/// The counts and timing of one scan position.
+#[derive(Debug, Serialize)]
+pub struct ScanPositionSummary {
+    pub name: String,
+    pub translations: usize,
+    pub counts: Counts,
+    pub seconds: f64,
+    pub histogram: Histogram,
+    /// The `--preview` images of the scan position's translations.
+    pub previews: Vec<String>,
+    /// The throughput of each translation that was colorized.
+    pub throughputs: Vec<Throughput>,
+}
+
+/// The wall time and point rates of one translation, for comparing machines and settings.
+#[derive(Debug, Serialize)]
+pub struct Throughput {
+    pub infile: String,
+    pub outfile: String,
+    pub seconds: f64,
+    pub read_per_second: f64,
+    pub written_per_second: f64,
+}
+
+/// A translation that couldn't be colorized with `--keep-going`.
+#[derive(Debug, Serialize)]
+pub struct Failure {
+    pub scan_position: String,
+    pub infile: String,
+    pub message: String,
+}
+
+/// The SHA-256 of an output file.
+#[derive(Debug, Serialize)]
+pub struct Checksum {
+    pub path: String,
+    pub sha256: String,
+}
+
+/// Totals across a whole run.
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    pub scan_positions: Vec<ScanPositionSummary>,
+    pub failures: Vec<Failure>,
+    pub checksums: Vec<Checksum>,
+    /// Image files that couldn't be linked to an image of their scan position, and weren't used.
+    pub unlinked_images: Vec<String>,
+    /// Images that couldn't be opened, and weren't used.
+    pub unreadable_images: Vec<String>,
+    pub total: Counts,
+    pub seconds: f64,
+}
+
+impl Counts {
+    /// Adds the other counts to these.
+    pub fn add(&mut self, other: &Counts) {
+        self.read += other.read;
+        self.written += other.written;
+        self.no_thermal += other.no_thermal;
+        self.behind_camera += other.behind_camera;
+        self.outside_images += other.outside_images;
+        self.invalid_pixels += other.invalid_pixels;
+        self.no_images += other.no_images;
+        self.too_few_images += other.too_few_images;
+        self.disagreement += other.disagreement;
+        self.no_baseline += other.no_baseline;
+        self.filtered += other.filtered;
+    }
+
+    /// Counts a point that was skipped because it had no temperature.
+    pub fn add_no_thermal(&mut self, reason: NoThermal) {
+        self.no_thermal += 1;
+        match reason {
+            NoThermal::NoImages => self.no_images += 1,
+            NoThermal::BehindCamera => self.behind_camera += 1,
+            NoThermal::OutsideImages => self.outside_images += 1,
+            NoThermal::InvalidPixels => self.invalid_pixels += 1,
+            NoThermal::TooFewImages => self.too_few_images += 1,
+            NoThermal::Disagreement => self.disagreement += 1,
+        }
+    }
+
+    /// Returns the number of points that were read but not written.
+    pub fn skipped(&self) -> u64 {
+        self.read - self.written
+    }
+}
+
+impl Throughput {
+    /// Creates the throughput of a translation from its counts and wall time.
+    pub fn new(infile: &Path, outfile: &Path, counts: &Counts, seconds: f64) -> Throughput {
+        let per_second = |count: u64| if seconds > 0. {
+            count as f64 / seconds
+        } else {
+            0.
+        };
+        Throughput {
+            infile: infile.display().to_string(),
+            outfile: outfile.display().to_string(),
+            seconds: seconds,
+            read_per_second: per_second(counts.read),
+            written_per_second: per_second(counts.written),
+        }
+    }
+}
+
+impl Histogram {
+    /// Creates an empty histogram over a domain.
+    pub fn new(min: f64, max: f64) -> Histogram {
+        Histogram {
+            min: min,
+            max: max,
+            bins: vec![0; HISTOGRAM_BINS],
+            below: 0,
+            above: 0,
+        }
+    }
+
+    /// Adds a temperature, ignoring NaN.
+    pub fn add(&mut self, temperature: f64) {
+        if temperature.is_nan() {
+            return;
+        }
+        if temperature < self.min {
+            self.below += 1;
+        } else if temperature > self.max {
+            self.above += 1;
+        } else {
+            let bins = self.bins.len();
+            let bin = ((temperature - self.min) / (self.max - self.min) * bins as f64) as usize;
+            self.bins[bin.min(bins - 1)] += 1;
+        }
+    }
+
+    /// Adds the other histogram's counts to this one, which must have the same domain.
+    pub fn merge(&mut self, other: &Histogram) {
+        for (bin, other) in self.bins.iter_mut().zip(other.bins.iter()) {
+            *bin += other;
+        }
+        self.below += other.below;
+        self.above += other.above;
+    }
+}
+
+impl Summary {
+    /// Adds a scan position to the summary and its counts to the totals.
+    pub fn add(&mut self, scan_position: ScanPositionSummary) {
+        self.total.add(&scan_position.counts);
+        self.scan_positions.push(scan_position);
+    }
+
+    /// Writes this summary as JSON.
+    pub fn write_to_path<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer_pretty(file, self).map_err(|err| {
+            io::Error::new(io::ErrorKind::Other, err)
+        })
+    }
+}
+
+impl fmt::Display for Counts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} read, {} written, {} skipped ({} without thermal, {} without baseline)",
+            self.read,
+            self.written,
+            self.skipped(),
+            self.no_thermal,
+            self.no_baseline
+        )?;
+        if self.no_thermal > 0 {
+            write!(
+                f,
+                ", without thermal: {} behind the camera, {} outside the images, {} on invalid \
+                 pixels, {} without images, {} in too few images, {} with disagreeing images",
+                self.behind_camera,
+                self.outside_images,
+                self.invalid_pixels,
+                self.no_images,
+                self.too_few_images,
+                self.disagreement
+            )?;
+        }
+        if self.filtered > 0 {
+            write!(f, ", {} filtered", self.filtered)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Throughput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:.0} points read/s, {:.0} points written/s, {:.1}s",
+            self.read_per_second,
+            self.written_per_second,
+            self.seconds
+        )
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Summary:")?;
+        for scan_position in &self.scan_positions {
+            writeln!(
+                f,
+                "  - {}: {} translations, {} in {:.1}s",
+                scan_position.name,
+                scan_position.translations,
+                scan_position.counts,
+                scan_position.seconds
+            )?;
+            for throughput in &scan_position.throughputs {
+                writeln!(f, "    - {}: {}", throughput.infile, throughput)?;
+            }
+        }
+        write!(f, "  - Total: {} in {:.1}s", self.total, self.seconds)?;
+        if !self.failures.is_empty() {
+            write!(f, "\n  - {} failures:", self.failures.len())?;
+            for failure in &self.failures {
+                write!(
+                    f,
+                    "\n    - {} ({}): {}",
+                    failure.infile,
+                    failure.scan_position,
+                    failure.message
+                )?;
+            }
+        }
+        if !self.unlinked_images.is_empty() {
+            write!(f, "\n  - {} unlinked images:", self.unlinked_images.len())?;
+            for path in &self.unlinked_images {
+                write!(f, "\n    - {}", path)?;
+            }
+        }
+        if !self.unreadable_images.is_empty() {
+            write!(f, "\n  - {} unreadable images:", self.unreadable_images.len())?;
+            for path in &self.unreadable_images {
+                write!(f, "\n    - {}", path)?;
+            }
+        }
+        Ok(())
+    }
+}
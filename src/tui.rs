@@ -0,0 +1,326 @@
+//! A full-screen status display for `--progress-format tui`, drawn from the progress events.
+
+use serde_json::Value;
+use std::cell::Cell;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+/// How often the screen is redrawn for point events.
+const REDRAW_INTERVAL_MS: u64 = 200;
+
+/// The most scan positions shown at once.
+const MAX_ROWS: usize = 20;
+
+/// The number of recent warnings and errors shown.
+const MAX_MESSAGES: usize = 5;
+
+lazy_static! {
+    /// The screen, shared by every thread that logs, e.g. the reader thread of a translation.
+    static ref SCREEN: Mutex<Option<Screen>> = Mutex::new(None);
+}
+
+/// True between the run's start and completion.
+static DRAWING: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// True while this thread is handling an event.
+    static HANDLING: Cell<bool> = Cell::new(false);
+}
+
+#[derive(Debug)]
+enum State {
+    Pending,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Debug)]
+struct Row {
+    name: String,
+    state: State,
+    /// Counts of the scan position's translations before the current one.
+    read: u64,
+    written: u64,
+    start: Option<Instant>,
+    seconds: f64,
+}
+
+#[derive(Debug)]
+struct Screen {
+    /// Whether the terminal has been cleared for the first draw.
+    cleared: bool,
+    start: Instant,
+    last_draw: Option<Instant>,
+    estimated_points: u64,
+    rows: Vec<Row>,
+    current: Option<usize>,
+    /// Counts of the current translation.
+    read: u64,
+    written: u64,
+    messages: Vec<String>,
+}
+
+/// Returns true if the screen is being drawn, i.e. between the run's start and completion.
+pub fn is_drawing() -> bool {
+    DRAWING.load(Ordering::SeqCst)
+}
+
+/// Updates the screen with a progress event, and redraws it.
+///
+/// The screen starts with the `run_started` event and is left on the terminal after `complete`.
+/// Each `run_started`, e.g. each colorization of `--watch`, starts a new screen.
+pub fn handle(event: &Value) {
+    // A panic while drawing comes back here through the panic hook, while we hold the lock.
+    if HANDLING.with(|handling| handling.replace(true)) {
+        return;
+    }
+    let _handling = Handling;
+    let mut screen = SCREEN.lock().unwrap_or_else(|err| err.into_inner());
+    let kind = event["event"].as_str().unwrap_or_default();
+    if kind == "run_started" {
+        *screen = Some(Screen::new(event));
+        DRAWING.store(true, Ordering::SeqCst);
+    }
+    let done = if let Some(screen) = screen.as_mut() {
+        screen.handle(kind, event);
+        if kind != "points" || screen.is_due() {
+            screen.draw();
+        }
+        kind == "complete"
+    } else {
+        false
+    };
+    if done {
+        *screen = None;
+        DRAWING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Clears `HANDLING` when an event is handled, even if drawing panics.
+struct Handling;
+
+impl Drop for Handling {
+    fn drop(&mut self) {
+        HANDLING.with(|handling| handling.set(false));
+    }
+}
+
+impl Screen {
+    fn new(event: &Value) -> Screen {
+        Screen {
+            cleared: false,
+            start: Instant::now(),
+            last_draw: None,
+            estimated_points: event["estimated_points"].as_u64().unwrap_or(0),
+            rows: event["scan_positions"]
+                .as_array()
+                .map(|names| {
+                    names
+                        .iter()
+                        .map(|name| Row {
+                            name: name.as_str().unwrap_or_default().to_string(),
+                            state: State::Pending,
+                            read: 0,
+                            written: 0,
+                            start: None,
+                            seconds: 0.,
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+            current: None,
+            read: 0,
+            written: 0,
+            messages: Vec::new(),
+        }
+    }
+
+    fn handle(&mut self, kind: &str, event: &Value) {
+        match kind {
+            "scan_position_started" => {
+                self.finish_scan_position();
+                let name = event["scan_position"].as_str().unwrap_or_default();
+                self.current = self.rows.iter().position(|row| row.name == name);
+                if let Some(row) = self.current_row() {
+                    row.state = State::Running;
+                    row.start = Some(Instant::now());
+                }
+            }
+            "translation_started" => self.finish_translation(),
+            "points" | "translation_finished" => {
+                self.read = event["read"].as_u64().unwrap_or(self.read);
+                self.written = event["written"].as_u64().unwrap_or(self.written);
+            }
+            "warning" | "error" => {
+                let message = event["message"].as_str().unwrap_or_default();
+                let prefix = if kind == "error" {
+                    if let Some(row) = self.current_row() {
+                        row.state = State::Failed;
+                    }
+                    "ERROR"
+                } else {
+                    "WARNING"
+                };
+                self.messages.push(format!("{}: {}", prefix, message));
+                if self.messages.len() > MAX_MESSAGES {
+                    self.messages.remove(0);
+                }
+            }
+            "complete" => {
+                self.finish_scan_position();
+                self.current = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn current_row(&mut self) -> Option<&mut Row> {
+        match self.current {
+            Some(index) => self.rows.get_mut(index),
+            None => None,
+        }
+    }
+
+    fn finish_translation(&mut self) {
+        let (read, written) = (self.read, self.written);
+        if let Some(row) = self.current_row() {
+            row.read += read;
+            row.written += written;
+        }
+        self.read = 0;
+        self.written = 0;
+    }
+
+    fn finish_scan_position(&mut self) {
+        self.finish_translation();
+        if let Some(row) = self.current_row() {
+            if let State::Running = row.state {
+                row.state = State::Done;
+            }
+            row.seconds = row.start.map(seconds).unwrap_or(0.);
+        }
+    }
+
+    fn is_due(&self) -> bool {
+        self.last_draw
+            .map(|last_draw| last_draw.elapsed() >= Duration::from_millis(REDRAW_INTERVAL_MS))
+            .unwrap_or(true)
+    }
+
+    fn draw(&mut self) {
+        self.last_draw = Some(Instant::now());
+        let stderr = io::stderr();
+        let mut stderr = stderr.lock();
+        // There's nowhere to report a failure to draw.
+        if !self.cleared {
+            let _ = stderr.write_all(b"\x1b[2J");
+            self.cleared = true;
+        }
+        let _ = stderr.write_all(self.render().as_bytes());
+        let _ = stderr.flush();
+    }
+
+    fn render(&self) -> String {
+        let mut lines = Vec::new();
+        let current = self.current;
+        let (read, written) = (self.read, self.written);
+        let total_read = self.rows.iter().map(|row| row.read).sum::<u64>() + read;
+        let finished = self.rows
+            .iter()
+            .filter(|row| match row.state {
+                State::Done | State::Failed => true,
+                _ => false,
+            })
+            .count();
+        let elapsed = seconds(self.start);
+        let rate = if elapsed > 0. {
+            total_read as f64 / elapsed
+        } else {
+            0.
+        };
+        let eta = if rate > 0. && self.estimated_points > total_read {
+            format_seconds((self.estimated_points - total_read) as f64 / rate)
+        } else {
+            "--:--:--".to_string()
+        };
+        lines.push(format!(
+            "tce: {} of {} scan positions, {} of ~{} points, {:.0} points/s, {} elapsed, ETA {}",
+            finished,
+            self.rows.len(),
+            total_read,
+            self.estimated_points,
+            rate,
+            format_seconds(elapsed),
+            eta
+        ));
+        lines.push(String::new());
+        let first = current
+            .unwrap_or(finished)
+            .saturating_sub(MAX_ROWS / 2)
+            .min(self.rows.len().saturating_sub(MAX_ROWS));
+        for (index, row) in self.rows.iter().enumerate().skip(first).take(MAX_ROWS) {
+            let (row_read, row_written, row_seconds) = if current == Some(index) {
+                (
+                    row.read + read,
+                    row.written + written,
+                    row.start.map(seconds).unwrap_or(0.),
+                )
+            } else {
+                (row.read, row.written, row.seconds)
+            };
+            let state = match row.state {
+                State::Pending => "pending",
+                State::Running => "running",
+                State::Done => "done",
+                State::Failed => "failed",
+            };
+            let throughput = if row_seconds > 0. {
+                format!("{:.0} points/s", row_read as f64 / row_seconds)
+            } else {
+                String::new()
+            };
+            lines.push(format!(
+                "  {:<16} {:<8} {:>12} read {:>12} written  {}",
+                row.name,
+                state,
+                row_read,
+                row_written,
+                throughput
+            ));
+        }
+        if self.rows.len() > MAX_ROWS {
+            lines.push(format!("  ({} scan positions not shown)", self.rows.len() - MAX_ROWS));
+        }
+        if !self.messages.is_empty() {
+            lines.push(String::new());
+            lines.extend(self.messages.iter().cloned());
+        }
+        // Move to the top left and overwrite each line, clearing the rest of it, then clear what's
+        // left below, rather than clearing the whole screen first, which flickers.
+        let mut s = "\x1b[H".to_string();
+        for line in lines {
+            s.push_str(&line);
+            s.push_str("\x1b[K\n");
+        }
+        s.push_str("\x1b[J");
+        s
+    }
+}
+
+fn seconds(instant: Instant) -> f64 {
+    let duration = instant.elapsed();
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 * 1e-9
+}
+
+fn format_seconds(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
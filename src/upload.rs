@@ -0,0 +1,71 @@
+//! Uploads outputs to S3-compatible object storage, for `--output-url`.
+
+use std::fmt;
+use std::io::{self, Error, ErrorKind};
+use std::path::Path;
+use std::process::Command;
+
+/// Where outputs are uploaded, e.g. `s3://bucket/prefix/`.
+///
+/// Files are uploaded with the AWS CLI (`aws s3 cp`), so credentials, regions, and endpoints for
+/// other object stores come from its usual configuration, e.g. `AWS_PROFILE` or
+/// `AWS_ENDPOINT_URL`.
+#[derive(Clone, Debug)]
+pub struct Upload {
+    url: String,
+}
+
+impl Upload {
+    /// Returns the url of a file at this relative path under the prefix.
+    pub fn url(&self, relative: &Path) -> String {
+        let key = relative
+            .iter()
+            .map(|component| component.to_string_lossy().into_owned())
+            .collect::<Vec<_>>()
+            .join("/");
+        format!("{}{}", self.url, key)
+    }
+
+    /// Uploads a local file to this relative path under the prefix, returning its url.
+    pub fn put(&self, path: &Path, relative: &Path) -> io::Result<String> {
+        let url = self.url(relative);
+        let output = Command::new("aws")
+            .args(&["s3", "cp", "--only-show-errors"])
+            .arg(path)
+            .arg(&url)
+            .output()?;
+        if output.status.success() {
+            Ok(url)
+        } else {
+            Err(Error::new(
+                ErrorKind::Other,
+                format!(
+                    "aws s3 cp exited with {}: {}",
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            ))
+        }
+    }
+}
+
+impl ::std::str::FromStr for Upload {
+    type Err = String;
+    /// Parses an `s3://` url, which is treated as a prefix.
+    fn from_str(s: &str) -> Result<Upload, String> {
+        if !s.starts_with("s3://") || s.len() <= "s3://".len() {
+            return Err(format!("Invalid output url (must be s3://bucket/prefix/): {}", s));
+        }
+        let mut url = s.to_string();
+        if !url.ends_with('/') {
+            url.push('/');
+        }
+        Ok(Upload { url: url })
+    }
+}
+
+impl fmt::Display for Upload {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.url)
+    }
+}
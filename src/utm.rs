@@ -0,0 +1,84 @@
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+const A: f64 = 6_378_137.;
+const F: f64 = 1. / 298.257_223_563;
+const K0: f64 = 0.9996;
+const FALSE_EASTING: f64 = 500_000.;
+const FALSE_NORTHING: f64 = 10_000_000.;
+
+/// A WGS84 UTM zone, e.g. `13N`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct UtmZone {
+    pub number: u8,
+    pub north: bool,
+}
+
+impl UtmZone {
+    /// Converts an easting and northing in this zone to a WGS84 longitude and latitude, in
+    /// degrees.
+    ///
+    /// Uses the series expansions from Snyder's "Map Projections: A Working Manual", which are
+    /// accurate to well under a millimeter within a zone.
+    pub fn to_wgs84(&self, easting: f64, northing: f64) -> (f64, f64) {
+        let e2 = F * (2. - F);
+        let ep2 = e2 / (1. - e2);
+        let x = easting - FALSE_EASTING;
+        let y = if self.north {
+            northing
+        } else {
+            northing - FALSE_NORTHING
+        };
+
+        let m = y / K0;
+        let mu = m / (A * (1. - e2 / 4. - 3. * e2.powi(2) / 64. - 5. * e2.powi(3) / 256.));
+        let e1 = (1. - (1. - e2).sqrt()) / (1. + (1. - e2).sqrt());
+        let phi1 = mu + (3. * e1 / 2. - 27. * e1.powi(3) / 32.) * (2. * mu).sin() +
+            (21. * e1.powi(2) / 16. - 55. * e1.powi(4) / 32.) * (4. * mu).sin() +
+            (151. * e1.powi(3) / 96.) * (6. * mu).sin() +
+            (1097. * e1.powi(4) / 512.) * (8. * mu).sin();
+
+        let (sin1, cos1, tan1) = (phi1.sin(), phi1.cos(), phi1.tan());
+        let n1 = A / (1. - e2 * sin1.powi(2)).sqrt();
+        let t1 = tan1.powi(2);
+        let c1 = ep2 * cos1.powi(2);
+        let r1 = A * (1. - e2) / (1. - e2 * sin1.powi(2)).powf(1.5);
+        let d = x / (n1 * K0);
+
+        let latitude = phi1 -
+            (n1 * tan1 / r1) *
+                (d.powi(2) / 2. -
+                     (5. + 3. * t1 + 10. * c1 - 4. * c1.powi(2) - 9. * ep2) * d.powi(4) / 24. +
+                     (61. + 90. * t1 + 298. * c1 + 45. * t1.powi(2) - 252. * ep2 -
+                          3. * c1.powi(2)) * d.powi(6) / 720.);
+        let longitude = (d - (1. + 2. * t1 + c1) * d.powi(3) / 6. +
+                             (5. - 2. * c1 + 28. * t1 - 3. * c1.powi(2) + 8. * ep2 +
+                                  24. * t1.powi(2)) * d.powi(5) / 120.) / cos1;
+        let central_meridian = f64::from(self.number) * 6. - 183.;
+        (
+            central_meridian + longitude * 180. / PI,
+            latitude * 180. / PI,
+        )
+    }
+}
+
+impl FromStr for UtmZone {
+    type Err = String;
+    fn from_str(s: &str) -> Result<UtmZone, String> {
+        let err = || format!("Invalid UTM zone (e.g. 13N or 55S): {}", s);
+        let s = s.trim();
+        let (number, hemisphere) = s.split_at(s.len().saturating_sub(1));
+        let north = match hemisphere {
+            "N" | "n" => true,
+            "S" | "s" => false,
+            _ => return Err(err()),
+        };
+        match number.parse::<u8>() {
+            Ok(number) if number >= 1 && number <= 60 => Ok(UtmZone {
+                number: number,
+                north: north,
+            }),
+            _ => Err(err()),
+        }
+    }
+}